@@ -1,3 +1,5 @@
+pub mod ocr;
+
 use std::error::Error;
 use std::fmt::Debug;
 use std::fs;
@@ -0,0 +1,222 @@
+use std::fmt;
+
+// Block-letter fonts used by the puzzles that draw text on a screen. Glyphs
+// are laid out in cells of `stride` columns; blank columns around a glyph are
+// ignored, so narrow (I) and wide (Y) letters still fit their cell.
+struct Font {
+    height: usize,
+    stride: usize,
+    glyphs: &'static [(char, &'static str)],
+}
+
+#[rustfmt::skip]
+const SMALL: Font = Font {
+    height: 6,
+    stride: 5,
+    glyphs: &[
+        ('A', ".##.\n#..#\n#..#\n####\n#..#\n#..#"),
+        ('B', "###.\n#..#\n###.\n#..#\n#..#\n###."),
+        ('C', ".##.\n#..#\n#...\n#...\n#..#\n.##."),
+        ('E', "####\n#...\n###.\n#...\n#...\n####"),
+        ('F', "####\n#...\n###.\n#...\n#...\n#..."),
+        ('G', ".##.\n#..#\n#...\n#.##\n#..#\n.###"),
+        ('H', "#..#\n#..#\n####\n#..#\n#..#\n#..#"),
+        ('I', "###\n.#.\n.#.\n.#.\n.#.\n###"),
+        ('J', "..##\n...#\n...#\n...#\n#..#\n.##."),
+        ('K', "#..#\n#.#.\n##..\n#.#.\n#.#.\n#..#"),
+        ('L', "#...\n#...\n#...\n#...\n#...\n####"),
+        ('O', ".##.\n#..#\n#..#\n#..#\n#..#\n.##."),
+        ('P', "###.\n#..#\n#..#\n###.\n#...\n#..."),
+        ('R', "###.\n#..#\n#..#\n###.\n#.#.\n#..#"),
+        ('S', ".###\n#...\n#...\n.##.\n...#\n###."),
+        ('U', "#..#\n#..#\n#..#\n#..#\n#..#\n.##."),
+        ('Y', "#...#\n#...#\n.#.#.\n..#..\n..#..\n..#.."),
+        ('Z', "####\n...#\n..#.\n.#..\n#...\n####"),
+    ],
+};
+
+#[rustfmt::skip]
+const LARGE: Font = Font {
+    height: 10,
+    stride: 8,
+    glyphs: &[
+        ('A', "..##..\n.#..#.\n#....#\n#....#\n#....#\n######\n#....#\n#....#\n#....#\n#....#"),
+        ('B', "#####.\n#....#\n#....#\n#....#\n#####.\n#....#\n#....#\n#....#\n#....#\n#####."),
+        ('C', ".####.\n#....#\n#.....\n#.....\n#.....\n#.....\n#.....\n#.....\n#....#\n.####."),
+        ('E', "######\n#.....\n#.....\n#.....\n#####.\n#.....\n#.....\n#.....\n#.....\n######"),
+        ('F', "######\n#.....\n#.....\n#.....\n#####.\n#.....\n#.....\n#.....\n#.....\n#....."),
+        ('G', ".####.\n#....#\n#.....\n#.....\n#.....\n#..###\n#....#\n#....#\n#...##\n.###.#"),
+        ('H', "#....#\n#....#\n#....#\n#....#\n######\n#....#\n#....#\n#....#\n#....#\n#....#"),
+        ('J', "...###\n....#.\n....#.\n....#.\n....#.\n....#.\n#...#.\n#...#.\n#...#.\n.###.."),
+        ('K', "#....#\n#...#.\n#..#..\n#.#...\n##....\n##....\n#.#...\n#..#..\n#...#.\n#....#"),
+        ('L', "#.....\n#.....\n#.....\n#.....\n#.....\n#.....\n#.....\n#.....\n#.....\n######"),
+        ('N', "#....#\n##...#\n##...#\n#.#..#\n#.#..#\n#..#.#\n#..#.#\n#...##\n#...##\n#....#"),
+        ('P', "#####.\n#....#\n#....#\n#....#\n#####.\n#.....\n#.....\n#.....\n#.....\n#....."),
+        ('R', "#####.\n#....#\n#....#\n#....#\n#####.\n#..#..\n#...#.\n#...#.\n#....#\n#....#"),
+        ('X', "#....#\n#....#\n.#..#.\n.#..#.\n..##..\n..##..\n.#..#.\n.#..#.\n#....#\n#....#"),
+        ('Z', "######\n.....#\n.....#\n....#.\n...#..\n..#...\n.#....\n#.....\n#.....\n######"),
+    ],
+};
+
+#[derive(Debug, PartialEq)]
+pub struct UnknownGlyph {
+    pub column: usize,
+    pub bitmap: String,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum OcrError {
+    UnsupportedHeight(usize),
+    RaggedGrid {
+        row: usize,
+    },
+    // `text` has a '?' in place of every glyph that could not be recognized.
+    UnknownGlyphs {
+        text: String,
+        glyphs: Vec<UnknownGlyph>,
+    },
+}
+
+impl fmt::Display for OcrError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            OcrError::UnsupportedHeight(height) => {
+                write!(f, "No font with letters {} pixels high", height)
+            }
+            OcrError::RaggedGrid { row } => write!(f, "Row {} has a different width", row),
+            OcrError::UnknownGlyphs { text, glyphs } => {
+                write!(f, "Unrecognized glyphs in {:?}", text)?;
+                for glyph in glyphs {
+                    write!(f, "\nColumn {}:\n{}", glyph.column, glyph.bitmap)?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+impl std::error::Error for OcrError {}
+
+// Reads the letters drawn on `grid`, indexed as `grid[y][x]`. The font is
+// picked from the grid height; blank cells are skipped.
+pub fn recognize<R: AsRef<[bool]>>(grid: &[R]) -> Result<String, OcrError> {
+    let font = [SMALL, LARGE]
+        .into_iter()
+        .find(|font| font.height == grid.len())
+        .ok_or(OcrError::UnsupportedHeight(grid.len()))?;
+    let width = grid[0].as_ref().len();
+    if let Some(row) = grid.iter().position(|row| row.as_ref().len() != width) {
+        return Err(OcrError::RaggedGrid { row });
+    }
+
+    let mut text = String::new();
+    let mut unknown = Vec::new();
+    for start in (0..width).step_by(font.stride) {
+        let end = (start + font.stride).min(width);
+        let lit = |x: usize| grid.iter().any(|row| row.as_ref()[x]);
+        let Some(first) = (start..end).find(|&x| lit(x)) else {
+            continue;
+        };
+        let last = (start..end).rev().find(|&x| lit(x)).unwrap();
+        let bitmap = grid
+            .iter()
+            .map(|row| {
+                row.as_ref()[first..=last]
+                    .iter()
+                    .map(|&pixel| if pixel { '#' } else { '.' })
+                    .collect::<String>()
+            })
+            .collect::<Vec<_>>()
+            .join("\n");
+        match font.glyphs.iter().find(|(_, glyph)| *glyph == bitmap) {
+            Some(&(letter, _)) => text.push(letter),
+            None => {
+                text.push('?');
+                unknown.push(UnknownGlyph {
+                    column: first,
+                    bitmap,
+                });
+            }
+        }
+    }
+
+    if unknown.is_empty() {
+        Ok(text)
+    } else {
+        Err(OcrError::UnknownGlyphs {
+            text,
+            glyphs: unknown,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn grid(picture: &str) -> Vec<Vec<bool>> {
+        picture
+            .lines()
+            .map(|line| line.chars().map(|c| c == '#').collect())
+            .collect()
+    }
+
+    // Draws `letters` side by side, each one padded to the font stride, after
+    // `blank` empty cells.
+    fn draw(font: &Font, letters: &str, blank: usize) -> Vec<Vec<bool>> {
+        let mut picture = vec![String::new(); font.height];
+        for letter in letters.chars() {
+            let (_, glyph) = font.glyphs.iter().find(|(c, _)| *c == letter).unwrap();
+            for (row, line) in picture.iter_mut().zip(glyph.lines()) {
+                row.push_str(&format!("{:.<1$}", line, font.stride));
+            }
+        }
+        let padding = ".".repeat(blank * font.stride);
+        grid(
+            &picture
+                .iter()
+                .map(|row| format!("{}{}", padding, row))
+                .collect::<Vec<_>>()
+                .join("\n"),
+        )
+    }
+
+    #[test]
+    fn test_small_font() {
+        assert_eq!(
+            recognize(&draw(&SMALL, "RURUCEOEIL", 0)),
+            Ok("RURUCEOEIL".to_string())
+        );
+        assert_eq!(recognize(&draw(&SMALL, "AIYZ", 2)), Ok("AIYZ".to_string()));
+    }
+
+    #[test]
+    fn test_large_font() {
+        assert_eq!(recognize(&draw(&LARGE, "NXGJ", 1)), Ok("NXGJ".to_string()));
+    }
+
+    #[test]
+    fn test_unknown_glyph() {
+        let picture = "\
+####.#..#
+#....#..#
+###..#..#
+#....####
+#.......#
+####....#";
+        assert_eq!(
+            recognize(&grid(picture)),
+            Err(OcrError::UnknownGlyphs {
+                text: "E?".to_string(),
+                glyphs: vec![UnknownGlyph {
+                    column: 5,
+                    bitmap: "#..#\n#..#\n#..#\n####\n...#\n...#".to_string(),
+                }],
+            })
+        );
+        assert_eq!(
+            recognize(&grid("#\n#")),
+            Err(OcrError::UnsupportedHeight(2))
+        );
+    }
+}
@@ -1,11 +1,28 @@
+mod render;
+mod segments;
+
 use once_cell::sync::Lazy;
+use std::collections::HashSet;
 use std::error::Error;
+use std::num::{IntErrorKind, ParseIntError};
 use std::str::FromStr;
 
 static INPUT: Lazy<Vec<Turn>> =
     Lazy::new(|| load_input("data/day01.txt").expect("Failed to load input"));
 
 fn main() {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if let Some(i) = args.iter().position(|arg| arg == "--svg") {
+        let path = args.get(i + 1).expect("Missing SVG output path");
+        let svg = render::svg(&trace(&INPUT), first_revisited(&INPUT));
+        std::fs::write(path, svg).expect("Failed to write SVG");
+    }
+    if args.iter().any(|arg| arg == "--ascii") {
+        println!(
+            "{}",
+            render::ascii(&trace(&INPUT), first_revisited(&INPUT), 100, 50)
+        );
+    }
     println!("Part 1: {}", part1(&INPUT));
     println!("Part 2: {}", part2(&INPUT));
 }
@@ -19,27 +36,54 @@ fn part1(input: &[Turn]) -> u32 {
 }
 
 fn part2(input: &[Turn]) -> u32 {
-    let mut visited = std::collections::HashSet::new();
+    let (x, y) = segments::first_revisited(input).expect("Should never get here");
+    (x.abs() + y.abs()) as u32
+}
+
+fn first_revisited(input: &[Turn]) -> Option<(i32, i32)> {
+    let mut visited = HashSet::new();
     visited.insert((0, 0));
     let mut state = State::default();
     for turn in input {
         let steps = state.step_with_trace(turn);
         for (x, y) in steps {
             if visited.contains(&(x, y)) {
-                return (x.abs() + y.abs()) as u32;
+                return Some((x, y));
             }
             visited.insert((x, y));
         }
     }
-    panic!("Should never get here");
+    None
+}
+
+// Every visited coordinate, starting at the origin.
+fn trace(input: &[Turn]) -> Vec<(i32, i32)> {
+    let mut state = State::default();
+    let mut points = vec![(0, 0)];
+    for turn in input {
+        points.extend(state.step_with_trace(turn));
+    }
+    points
 }
 
 fn load_input(path: &str) -> Result<Vec<Turn>, Box<dyn Error>> {
-    common::read_file_as_string(path)?
-        .split(", ")
-        .map(|s| {
-            s.parse()
-                .map_err(|e| Box::<dyn Error>::from(format!("{:?}", e)))
+    Ok(parse_turns(&common::read_file_as_string(path)?)?)
+}
+
+// Instructions may be separated by commas, whitespace or both. The walk is
+// limited to i32::MAX steps in total, so no coordinate or distance overflows.
+fn parse_turns(input: &str) -> Result<Vec<Turn>, TurnParseError> {
+    let mut total = 0_u32;
+    input
+        .split(|c: char| c == ',' || c.is_whitespace())
+        .filter(|token| !token.is_empty())
+        .map(|token| {
+            let turn: Turn = token.parse()?;
+            total = total
+                .checked_add(turn.steps())
+                .filter(|&total| total <= i32::MAX as u32)
+                .ok_or_else(|| TurnParseError::TooManySteps(token.to_string()))?;
+            Ok(turn)
         })
         .collect()
 }
@@ -54,10 +98,17 @@ struct State {
 impl State {
     fn step(&mut self, turn: &Turn) {
         self.heading = self.heading.turn(turn);
-        self.forward(turn.steps())
+        self.advance(self.moving(turn), turn.steps())
     }
-    fn forward(&mut self, steps: u32) {
-        match self.heading {
+    // Backing up keeps the heading but moves the opposite way.
+    fn moving(&self, turn: &Turn) -> Direction {
+        match turn {
+            Turn::Back(_) => self.heading.reverse(),
+            _ => self.heading,
+        }
+    }
+    fn advance(&mut self, direction: Direction, steps: u32) {
+        match direction {
             Direction::North => self.y += steps as i32,
             Direction::East => self.x += steps as i32,
             Direction::South => self.y -= steps as i32,
@@ -66,10 +117,10 @@ impl State {
     }
     fn step_with_trace(&mut self, turn: &Turn) -> Vec<(i32, i32)> {
         self.heading = self.heading.turn(turn);
-        self.forward_with_trace(turn.steps())
+        self.advance_with_trace(self.moving(turn), turn.steps())
     }
-    fn forward_with_trace(&mut self, steps: u32) -> Vec<(i32, i32)> {
-        match self.heading {
+    fn advance_with_trace(&mut self, direction: Direction, steps: u32) -> Vec<(i32, i32)> {
+        match direction {
             Direction::North => {
                 let trace = (self.y + 1..=self.y + steps as i32)
                     .map(|y| (self.x, y))
@@ -115,6 +166,7 @@ enum Direction {
     West,
 }
 
+#[allow(clippy::derivable_impls)]
 impl Default for Direction {
     fn default() -> Self {
         Direction::North
@@ -126,8 +178,13 @@ impl Direction {
         match turn {
             Turn::Left(_) => self.left(),
             Turn::Right(_) => self.right(),
+            Turn::Forward(_) | Turn::Back(_) => *self,
+            Turn::Reverse(_) => self.reverse(),
         }
     }
+    fn reverse(&self) -> Self {
+        self.left().left()
+    }
     fn left(&self) -> Self {
         match self {
             Direction::North => Direction::West,
@@ -145,18 +202,41 @@ impl Direction {
         }
     }
 }
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq)]
 enum Turn {
     Left(u32),
     Right(u32),
+    Forward(u32),
+    Back(u32),
+    Reverse(u32),
 }
 
-#[derive(Debug)]
-struct TurnParseError(String);
+#[derive(Debug, PartialEq)]
+enum TurnParseError {
+    Empty,
+    UnknownInstruction(String),
+    MissingSteps(String),
+    InvalidSteps(String),
+    TooManySteps(String),
+}
 
 impl std::fmt::Display for TurnParseError {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "Turn parse error: {}", self.0)
+        match self {
+            TurnParseError::Empty => write!(f, "Turn parse error: empty instruction"),
+            TurnParseError::UnknownInstruction(token) => {
+                write!(f, "Turn parse error: unknown instruction in {:?}", token)
+            }
+            TurnParseError::MissingSteps(token) => {
+                write!(f, "Turn parse error: missing steps in {:?}", token)
+            }
+            TurnParseError::InvalidSteps(token) => {
+                write!(f, "Turn parse error: invalid steps in {:?}", token)
+            }
+            TurnParseError::TooManySteps(token) => {
+                write!(f, "Turn parse error: too many steps in {:?}", token)
+            }
+        }
     }
 }
 
@@ -167,13 +247,29 @@ impl FromStr for Turn {
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
         let s = s.trim();
-        let steps = &s[1..].parse::<u32>().unwrap();
-        if s.starts_with("L") {
-            Ok(Turn::Left(*steps))
-        } else if s.starts_with("R") {
-            Ok(Turn::Right(*steps))
-        } else {
-            Err(TurnParseError(s.to_string()))
+        let mut chars = s.chars();
+        let instruction = chars.next().ok_or(TurnParseError::Empty)?;
+        let digits = chars.as_str();
+        if digits.is_empty() {
+            return Err(TurnParseError::MissingSteps(s.to_string()));
+        }
+        if !digits.bytes().all(|b| b.is_ascii_digit()) {
+            return Err(TurnParseError::InvalidSteps(s.to_string()));
+        }
+        let steps: u32 = digits.parse().map_err(|e: ParseIntError| match e.kind() {
+            IntErrorKind::PosOverflow => TurnParseError::TooManySteps(s.to_string()),
+            _ => TurnParseError::InvalidSteps(s.to_string()),
+        })?;
+        if steps > i32::MAX as u32 {
+            return Err(TurnParseError::TooManySteps(s.to_string()));
+        }
+        match instruction {
+            'L' => Ok(Turn::Left(steps)),
+            'R' => Ok(Turn::Right(steps)),
+            'F' => Ok(Turn::Forward(steps)),
+            'B' => Ok(Turn::Back(steps)),
+            'U' => Ok(Turn::Reverse(steps)),
+            _ => Err(TurnParseError::UnknownInstruction(s.to_string())),
         }
     }
 }
@@ -181,8 +277,11 @@ impl FromStr for Turn {
 impl Turn {
     fn steps(&self) -> u32 {
         match self {
-            Turn::Left(steps) => *steps,
-            Turn::Right(steps) => *steps,
+            Turn::Left(steps)
+            | Turn::Right(steps)
+            | Turn::Forward(steps)
+            | Turn::Back(steps)
+            | Turn::Reverse(steps) => *steps,
         }
     }
 }
@@ -216,6 +315,104 @@ mod tests {
         assert_eq!(part2(&input), 4);
     }
 
+    #[test]
+    fn test_trace() {
+        let input = vec![Right(8), Right(4), Right(4), Right(8)];
+        let points = trace(&input);
+        assert_eq!(points.len(), 25);
+        assert_eq!(points.last(), Some(&(4, 4)));
+        assert_eq!(first_revisited(&input), Some((4, 0)));
+        assert_eq!(first_revisited(&[Right(2), Left(3)]), None);
+    }
+
+    #[test]
+    fn test_render_ascii() {
+        let input = vec![Right(8), Right(4), Right(4), Right(8)];
+        let ascii = render::ascii(&trace(&input), first_revisited(&input), 100, 50);
+        let expected = [
+            "    E",
+            "    #",
+            "    #",
+            "    #",
+            "S###X####",
+            "    #   #",
+            "    #   #",
+            "    #   #",
+            "    #####",
+        ];
+        assert_eq!(ascii, expected.join("\n"));
+
+        let scaled = render::ascii(&trace(&input), None, 3, 5);
+        assert_eq!(scaled.lines().count(), 3);
+        assert!(scaled.lines().all(|line| line.len() <= 3));
+        assert_eq!(render::ascii(&trace(&input), None, 0, 5), "");
+        assert_eq!(render::ascii(&trace(&input), None, 3, 0), "");
+    }
+
+    #[test]
+    fn test_render_svg() {
+        let input = vec![Right(2), Left(3)];
+        let svg = render::svg(&trace(&input), None);
+        assert!(svg.starts_with("<svg"));
+        assert!(svg.contains(r#"points="0,0 1,0 2,0 2,-1 2,-2 2,-3""#));
+        assert!(svg.contains(r#"<circle cx="2" cy="-3" r="1" fill="red"/>"#));
+        assert!(!svg.contains("blue"));
+    }
+
+    #[test]
+    fn test_parse_turns() {
+        assert_eq!(
+            parse_turns("R2, L3,F10\n B4  U123"),
+            Ok(vec![Right(2), Left(3), Forward(10), Back(4), Reverse(123)])
+        );
+        assert_eq!(parse_turns(""), Ok(vec![]));
+        assert_eq!("".parse::<Turn>(), Err(TurnParseError::Empty));
+        assert_eq!(
+            "R".parse::<Turn>(),
+            Err(TurnParseError::MissingSteps("R".to_string()))
+        );
+        assert_eq!(
+            "X3".parse::<Turn>(),
+            Err(TurnParseError::UnknownInstruction("X3".to_string()))
+        );
+        assert_eq!(
+            "ñ3".parse::<Turn>(),
+            Err(TurnParseError::UnknownInstruction("ñ3".to_string()))
+        );
+        assert_eq!(
+            "L+3".parse::<Turn>(),
+            Err(TurnParseError::InvalidSteps("L+3".to_string()))
+        );
+        assert_eq!(
+            "L99999999999".parse::<Turn>(),
+            Err(TurnParseError::TooManySteps("L99999999999".to_string()))
+        );
+        assert_eq!(
+            "R4294967295".parse::<Turn>(),
+            Err(TurnParseError::TooManySteps("R4294967295".to_string()))
+        );
+        assert_eq!(
+            parse_turns("R2147483647, R2147483647"),
+            Err(TurnParseError::TooManySteps("R2147483647".to_string()))
+        );
+        assert_eq!(
+            parse_turns("R2147483646, R1").map(|turns| part1(&turns)),
+            Ok(i32::MAX as u32)
+        );
+    }
+
+    #[test]
+    fn test_extended_instructions() {
+        assert_eq!(part1(&[Forward(3), Back(5)]), 2);
+        assert_eq!(part1(&[Right(3), Reverse(1), Left(2)]), 4);
+        assert_eq!(
+            trace(&[Forward(2), Back(1)]),
+            vec![(0, 0), (0, 1), (0, 2), (0, 1)]
+        );
+        assert_eq!(part2(&[Forward(2), Reverse(1)]), 1);
+        assert_eq!(part2(&[Right(2), Back(3)]), 1);
+    }
+
     #[test]
     fn test_part1() {
         assert_eq!(part1(&INPUT), 300);
@@ -0,0 +1,94 @@
+use std::fmt::Write;
+
+type Point = (i32, i32);
+
+fn bounds(points: &[Point]) -> (i32, i32, i32, i32) {
+    let min_x = points.iter().map(|p| p.0).min().unwrap_or(0);
+    let max_x = points.iter().map(|p| p.0).max().unwrap_or(0);
+    let min_y = points.iter().map(|p| p.1).min().unwrap_or(0);
+    let max_y = points.iter().map(|p| p.1).max().unwrap_or(0);
+    (min_x, max_x, min_y, max_y)
+}
+
+// North is up, so y is negated when going to SVG coordinates.
+pub fn svg(points: &[Point], revisited: Option<Point>) -> String {
+    let (min_x, max_x, min_y, max_y) = bounds(points);
+    let mut out = String::new();
+    writeln!(
+        out,
+        r#"<svg xmlns="http://www.w3.org/2000/svg" viewBox="{} {} {} {}">"#,
+        min_x - 2,
+        -max_y - 2,
+        max_x - min_x + 4,
+        max_y - min_y + 4
+    )
+    .unwrap();
+    let polyline: Vec<String> = points
+        .iter()
+        .map(|(x, y)| format!("{},{}", x, -y))
+        .collect();
+    writeln!(
+        out,
+        r#"  <polyline points="{}" fill="none" stroke="black" stroke-width="1" vector-effect="non-scaling-stroke"/>"#,
+        polyline.join(" ")
+    )
+    .unwrap();
+    let markers = [
+        (points.first().copied(), "green"),
+        (points.last().copied(), "red"),
+        (revisited, "blue"),
+    ];
+    for (point, colour) in markers {
+        if let Some((x, y)) = point {
+            writeln!(
+                out,
+                r#"  <circle cx="{}" cy="{}" r="1" fill="{}"/>"#,
+                x, -y, colour
+            )
+            .unwrap();
+        }
+    }
+    out.push_str("</svg>\n");
+    out
+}
+
+// Scales the walk down to fit in `width` x `height` characters: `S` is the
+// start, `E` the end, `X` the first revisited point and `#` the rest of the path.
+// An empty area renders as an empty string.
+pub fn ascii(points: &[Point], revisited: Option<Point>, width: usize, height: usize) -> String {
+    if width == 0 || height == 0 {
+        return String::new();
+    }
+    let (min_x, max_x, min_y, max_y) = bounds(points);
+    let span_x = (max_x - min_x) as usize + 1;
+    let span_y = (max_y - min_y) as usize + 1;
+    let scale = span_x.div_ceil(width).max(span_y.div_ceil(height)).max(1);
+    let columns = span_x.div_ceil(scale);
+    let rows = span_y.div_ceil(scale);
+    let cell = |(x, y): Point| {
+        let column = (x - min_x) as usize / scale;
+        let row = rows - 1 - (y - min_y) as usize / scale;
+        (row, column)
+    };
+
+    let mut grid = vec![vec![' '; columns]; rows];
+    for &point in points {
+        let (row, column) = cell(point);
+        grid[row][column] = '#';
+    }
+    let markers = [
+        (points.first().copied(), 'S'),
+        (points.last().copied(), 'E'),
+        (revisited, 'X'),
+    ];
+    for (point, mark) in markers {
+        if let Some(point) = point {
+            let (row, column) = cell(point);
+            grid[row][column] = mark;
+        }
+    }
+    grid.into_iter()
+        .map(|row| row.into_iter().collect::<String>().trim_end().to_string())
+        .collect::<Vec<_>>()
+        .join("\n")
+}
@@ -0,0 +1,254 @@
+use crate::{State, Turn};
+use std::collections::{BTreeMap, BTreeSet};
+
+// Finds the first revisited point by intersecting whole segments instead of
+// expanding every unit step, so the cost depends on the number of turns only:
+// O(n log n) for n turns.
+//
+// Every segment holds the points it visits without its starting point, which
+// belongs to the previous segment, and the origin is a segment of its own. The
+// first revisit lies on the first segment meeting an earlier one, that is the
+// smallest `max(a, b)` over all pairs of meeting segments. One sweep finds it
+// for the crossing pairs and one pass per line for the collinear ones; the
+// point is then the one nearest to the start of that segment.
+pub fn first_revisited(input: &[Turn]) -> Option<(i32, i32)> {
+    let segments = segments(input);
+    let first = [first_crossing(&segments), first_overlap(&segments)]
+        .into_iter()
+        .flatten()
+        .min()?;
+    let segment = &segments[first];
+    let position = segments[..first]
+        .iter()
+        .filter_map(|earlier| segment.first_common(earlier))
+        .min_by_key(|&position| (position - segment.from).abs())?;
+    Some(if segment.vertical {
+        (segment.line, position)
+    } else {
+        (position, segment.line)
+    })
+}
+
+// The points `lo..=hi` of a line: x = `line` for a vertical segment, y = `line`
+// for a horizontal one. `from` is where the walk entered the segment.
+struct Segment {
+    vertical: bool,
+    line: i32,
+    lo: i32,
+    hi: i32,
+    from: i32,
+}
+
+impl Segment {
+    fn new(vertical: bool, line: i32, from: i32, to: i32) -> Self {
+        let (lo, hi) = if from < to {
+            (from + 1, to)
+        } else {
+            (to, from - 1)
+        };
+        Self {
+            vertical,
+            line,
+            lo,
+            hi,
+            from,
+        }
+    }
+
+    // Position on this segment of the point it shares with `other` that the
+    // walk reaches first.
+    fn first_common(&self, other: &Segment) -> Option<i32> {
+        if self.vertical != other.vertical {
+            let crosses = (self.lo..=self.hi).contains(&other.line)
+                && (other.lo..=other.hi).contains(&self.line);
+            return crosses.then_some(other.line);
+        }
+        let (lo, hi) = (self.lo.max(other.lo), self.hi.min(other.hi));
+        if self.line != other.line || lo > hi {
+            None
+        } else if self.from <= self.lo {
+            Some(lo)
+        } else {
+            Some(hi)
+        }
+    }
+}
+
+fn segments(input: &[Turn]) -> Vec<Segment> {
+    let mut state = State::default();
+    // The origin is visited before the first step.
+    let mut segments = vec![Segment {
+        vertical: true,
+        line: 0,
+        lo: 0,
+        hi: 0,
+        from: 0,
+    }];
+    for turn in input {
+        let from = (state.x, state.y);
+        state.step(turn);
+        let to = (state.x, state.y);
+        if from.1 == to.1 && from.0 != to.0 {
+            segments.push(Segment::new(false, from.1, from.0, to.0));
+        } else if from.0 == to.0 && from.1 != to.1 {
+            segments.push(Segment::new(true, from.0, from.1, to.1));
+        }
+    }
+    segments
+}
+
+// Smallest `max(a, b)` over the horizontal segments `a` crossed by vertical
+// segments `b`. Sweeping along x, the horizontal segments under the sweep line
+// are kept by y, and every vertical segment looks up the earliest of them
+// within its y range: that one makes the smallest pair it belongs to.
+fn first_crossing(segments: &[Segment]) -> Option<usize> {
+    let mut ys: Vec<i32> = segments
+        .iter()
+        .filter(|s| !s.vertical)
+        .map(|s| s.line)
+        .collect();
+    ys.sort_unstable();
+    ys.dedup();
+
+    // At the same x, horizontal segments start before vertical ones are
+    // looked up, and end after.
+    let mut events = Vec::with_capacity(2 * segments.len());
+    for (index, s) in segments.iter().enumerate() {
+        if s.vertical {
+            events.push((s.line, 1, index));
+        } else {
+            events.push((s.lo, 0, index));
+            events.push((s.hi, 2, index));
+        }
+    }
+    events.sort_unstable();
+
+    let mut by_y = vec![BTreeSet::new(); ys.len()];
+    let mut earliest = MinTree::new(ys.len());
+    let mut first: Option<usize> = None;
+    for (_, kind, index) in events {
+        let s = &segments[index];
+        if kind == 1 {
+            let lo = ys.partition_point(|&y| y < s.lo);
+            let hi = ys.partition_point(|&y| y <= s.hi);
+            if let Some(other) = earliest.min(lo, hi) {
+                let pair = index.max(other);
+                first = Some(first.map_or(pair, |first| first.min(pair)));
+            }
+        } else {
+            let y = ys.binary_search(&s.line).unwrap();
+            if kind == 0 {
+                by_y[y].insert(index);
+            } else {
+                by_y[y].remove(&index);
+            }
+            earliest.set(y, by_y[y].first().copied());
+        }
+    }
+    first
+}
+
+// First segment overlapping an earlier one on the same line. Until then the
+// segments of a line are disjoint, so only the one starting last before the
+// new segment ends can overlap it.
+fn first_overlap(segments: &[Segment]) -> Option<usize> {
+    let mut lines: BTreeMap<(bool, i32), BTreeMap<i32, i32>> = BTreeMap::new();
+    for (index, s) in segments.iter().enumerate() {
+        let intervals = lines.entry((s.vertical, s.line)).or_default();
+        if let Some((_, &hi)) = intervals.range(..=s.hi).next_back()
+            && hi >= s.lo
+        {
+            return Some(index);
+        }
+        intervals.insert(s.lo, s.hi);
+    }
+    None
+}
+
+// Minimum over a range of slots, each holding an optional index.
+struct MinTree {
+    size: usize,
+    nodes: Vec<usize>,
+}
+
+impl MinTree {
+    fn new(size: usize) -> Self {
+        Self {
+            size,
+            nodes: vec![usize::MAX; 2 * size],
+        }
+    }
+
+    fn set(&mut self, slot: usize, value: Option<usize>) {
+        let mut node = slot + self.size;
+        self.nodes[node] = value.unwrap_or(usize::MAX);
+        while node > 1 {
+            node /= 2;
+            self.nodes[node] = self.nodes[2 * node].min(self.nodes[2 * node + 1]);
+        }
+    }
+
+    // Smallest value in the slots `lo..hi`.
+    fn min(&self, lo: usize, hi: usize) -> Option<usize> {
+        let (mut lo, mut hi) = (lo + self.size, hi + self.size);
+        let mut best = usize::MAX;
+        while lo < hi {
+            if lo % 2 == 1 {
+                best = best.min(self.nodes[lo]);
+                lo += 1;
+            }
+            if hi % 2 == 1 {
+                hi -= 1;
+                best = best.min(self.nodes[hi]);
+            }
+            lo /= 2;
+            hi /= 2;
+        }
+        (best != usize::MAX).then_some(best)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+
+    fn turn() -> impl Strategy<Value = Turn> {
+        (0..5, 0..12_u32).prop_map(|(kind, steps)| match kind {
+            0 => Turn::Left(steps),
+            1 => Turn::Right(steps),
+            2 => Turn::Forward(steps),
+            3 => Turn::Back(steps),
+            _ => Turn::Reverse(steps),
+        })
+    }
+
+    #[test]
+    fn test_example() {
+        let input = [
+            Turn::Right(8),
+            Turn::Right(4),
+            Turn::Right(4),
+            Turn::Right(8),
+        ];
+        assert_eq!(first_revisited(&input), Some((4, 0)));
+    }
+
+    #[test]
+    fn test_back_to_origin() {
+        let input = [
+            Turn::Right(2),
+            Turn::Right(2),
+            Turn::Right(2),
+            Turn::Right(5),
+        ];
+        assert_eq!(first_revisited(&input), Some((0, 0)));
+    }
+
+    proptest! {
+        #[test]
+        fn test_matches_trace(input in proptest::collection::vec(turn(), 0..40)) {
+            prop_assert_eq!(first_revisited(&input), crate::first_revisited(&input));
+        }
+    }
+}
@@ -1,55 +1,151 @@
 use once_cell::sync::Lazy;
+use std::collections::{HashMap, VecDeque};
 
 static INPUT: Lazy<Instructions> =
     Lazy::new(|| load_input("data/day02.txt").expect("Failed to load input"));
 
-const KEYPAD1: [&str; 5] = [
-    "     ",
-    " 123 ",
-    " 456 ",
-    " 789 ",
-    "     ",
+#[rustfmt::skip]
+const KEYPAD1: [&str; 3] = [
+    "123",
+    "456",
+    "789",
 ];
 
-const KEYPAD2: [&str; 7] = [
-    "       ",
-    "   1   ",
-    "  234  ",
-    " 56789 ",
-    "  ABC  ",
-    "   D   ",
-    "       ",
+#[rustfmt::skip]
+const KEYPAD2: [&str; 5] = [
+    "  1",
+    " 234",
+    "56789",
+    " ABC",
+    "  D",
 ];
 
 fn main() {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let start = match args.iter().position(|arg| arg == "--start") {
+        Some(j) => args
+            .get(j + 1)
+            .and_then(|key| key.chars().next())
+            .expect("Missing start key"),
+        None => '5',
+    };
+    let custom = args.iter().position(|arg| arg == "--keypad").map(|i| {
+        let path = args.get(i + 1).expect("Missing keypad file");
+        Keypad::from_file(path, start).expect("Invalid keypad")
+    });
+    if let Some(i) = args.iter().position(|arg| arg == "--solve") {
+        let code = args.get(i + 1).expect("Missing code");
+        let keypad = match custom {
+            Some(keypad) => keypad,
+            None => Keypad::new(&KEYPAD2, start).expect("Invalid start key"),
+        };
+        for (key, lines) in code
+            .chars()
+            .zip(keypad.instructions_for(code).expect("Invalid code"))
+        {
+            println!("{}: {}", key, lines.join(" | "));
+        }
+        return;
+    }
+    if let Some(keypad) = custom {
+        println!("Code: {}", keypad.bathroom_code(&INPUT));
+        return;
+    }
     println!("Part 1: {}", part1(&INPUT));
     println!("Part 2: {}", part2(&INPUT));
 }
 
 fn part1(instructions: &Instructions) -> String {
-    Keypad(KEYPAD1.to_vec()).bathroom_code(&instructions)
+    Keypad::new(&KEYPAD1, '5')
+        .unwrap()
+        .bathroom_code(instructions)
 }
 
 fn part2(instructions: &Instructions) -> String {
-    Keypad(KEYPAD2.to_vec()).bathroom_code(&instructions)
+    Keypad::new(&KEYPAD2, '5')
+        .unwrap()
+        .bathroom_code(instructions)
+}
+
+#[derive(Debug, PartialEq)]
+enum KeypadError {
+    Empty,
+    DuplicateKey(char),
+    MissingStart(char),
+    UnknownKey(char),
+}
+
+impl std::fmt::Display for KeypadError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            KeypadError::Empty => write!(f, "Keypad has no keys"),
+            KeypadError::DuplicateKey(key) => write!(f, "Key {:?} appears more than once", key),
+            KeypadError::MissingStart(key) => write!(f, "Start key {:?} is not on the keypad", key),
+            KeypadError::UnknownKey(key) => write!(f, "Key {:?} is not on the keypad", key),
+        }
+    }
 }
 
-struct Keypad(Vec<&'static str>);
+impl std::error::Error for KeypadError {}
+
+// Spaces are gaps in the layout. Rows are padded to the same width and the
+// whole layout is surrounded by a ring of gaps, so moves never leave the grid.
+#[derive(Debug)]
+struct Keypad {
+    rows: Vec<Vec<char>>,
+    start: Position,
+}
 
 impl Keypad {
-    fn starting(&self, digit: char) -> Position {
-        for (y, line) in self.0.iter().enumerate() {
-            for (x, c) in line.chars().enumerate() {
-                if c == digit {
-                    return Position { x, y };
+    fn new<T: AsRef<str>>(layout: &[T], start: char) -> Result<Self, KeypadError> {
+        let width = layout
+            .iter()
+            .map(|line| line.as_ref().chars().count())
+            .max()
+            .unwrap_or(0);
+        let mut rows = vec![vec![' '; width + 2]];
+        for line in layout {
+            let mut row = vec![' '];
+            row.extend(line.as_ref().chars());
+            row.resize(width + 2, ' ');
+            rows.push(row);
+        }
+        rows.push(vec![' '; width + 2]);
+
+        let mut seen = std::collections::HashSet::new();
+        let mut start_position = None;
+        for (y, row) in rows.iter().enumerate() {
+            for (x, &key) in row.iter().enumerate() {
+                if key == ' ' {
+                    continue;
+                }
+                if !seen.insert(key) {
+                    return Err(KeypadError::DuplicateKey(key));
+                }
+                if key == start {
+                    start_position = Some(Position { x, y });
                 }
             }
         }
-        panic!("Invalid digit");
+        if seen.is_empty() {
+            return Err(KeypadError::Empty);
+        }
+        let start = start_position.ok_or(KeypadError::MissingStart(start))?;
+        Ok(Self { rows, start })
+    }
+
+    fn parse(layout: &str, start: char) -> Result<Self, KeypadError> {
+        let lines: Vec<&str> = layout.lines().collect();
+        Self::new(&lines, start)
+    }
+
+    fn from_file(path: &str, start: char) -> Result<Self, Box<dyn std::error::Error>> {
+        let layout = std::fs::read_to_string(path)?;
+        Ok(Self::parse(&layout, start)?)
     }
 
     fn char_at(&self, pos: &Position) -> char {
-        self.0[pos.y].chars().nth(pos.x).unwrap()
+        self.rows[pos.y][pos.x]
     }
 
     fn is_valid(&self, pos: &Position) -> bool {
@@ -57,7 +153,7 @@ impl Keypad {
     }
 
     fn bathroom_code(&self, instructions: &Instructions) -> String {
-        let mut position = self.starting('5');
+        let mut position = self.start;
         let mut code = String::new();
         for line in &instructions.0 {
             code.push(self.bathroom_digit(line, &mut position));
@@ -78,9 +174,81 @@ impl Keypad {
             *position = new_pos;
         }
     }
+
+    fn position_of(&self, key: char) -> Option<Position> {
+        self.rows.iter().enumerate().find_map(|(y, row)| {
+            row.iter()
+                .position(|&c| c == key && c != ' ')
+                .map(|x| Position { x, y })
+        })
+    }
+
+    // For every key of `code`, all the shortest lines that reach it from the
+    // previous key (the start key for the first one).
+    fn instructions_for(&self, code: &str) -> Result<Vec<Vec<String>>, KeypadError> {
+        let mut from = self.start;
+        let mut lines = Vec::new();
+        for key in code.chars() {
+            let to = self.position_of(key).ok_or(KeypadError::UnknownKey(key))?;
+            lines.push(self.shortest_lines(from, to));
+            from = to;
+        }
+        Ok(lines)
+    }
+
+    // Breadth-first search from `from` using the same moves as `try_move`, then
+    // every shortest route is rebuilt backwards from `to` through the positions
+    // one step closer to the start. Moves that are ignored never shorten a
+    // route, so they never appear in the result.
+    fn shortest_lines(&self, from: Position, to: Position) -> Vec<String> {
+        let mut distances = HashMap::from([(from, 0)]);
+        let mut queue = VecDeque::from([from]);
+        while let Some(position) = queue.pop_front() {
+            if position == to {
+                break;
+            }
+            for movement in &Movement::ALL {
+                let mut next = position;
+                self.try_move(movement, &mut next);
+                if !distances.contains_key(&next) {
+                    distances.insert(next, distances[&position] + 1);
+                    queue.push_back(next);
+                }
+            }
+        }
+
+        let mut lines = Vec::new();
+        let mut suffix = Vec::new();
+        self.collect_lines(to, &distances, &mut suffix, &mut lines);
+        lines.sort();
+        lines
+    }
+
+    fn collect_lines(
+        &self,
+        position: Position,
+        distances: &HashMap<Position, usize>,
+        suffix: &mut Vec<char>,
+        lines: &mut Vec<String>,
+    ) {
+        let distance = distances[&position];
+        if distance == 0 {
+            lines.push(suffix.iter().rev().collect());
+            return;
+        }
+        for movement in &Movement::ALL {
+            let previous = movement.opposite().next_position(&position);
+            if !self.is_valid(&previous) || distances.get(&previous) != Some(&(distance - 1)) {
+                continue;
+            }
+            suffix.push(movement.symbol());
+            self.collect_lines(previous, distances, suffix, lines);
+            suffix.pop();
+        }
+    }
 }
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 struct Position {
     x: usize,
     y: usize,
@@ -112,6 +280,31 @@ enum Movement {
 }
 
 impl Movement {
+    const ALL: [Movement; 4] = [
+        Movement::Up,
+        Movement::Down,
+        Movement::Left,
+        Movement::Right,
+    ];
+
+    fn symbol(&self) -> char {
+        match self {
+            Movement::Up => 'U',
+            Movement::Down => 'D',
+            Movement::Left => 'L',
+            Movement::Right => 'R',
+        }
+    }
+
+    fn opposite(&self) -> Movement {
+        match self {
+            Movement::Up => Movement::Down,
+            Movement::Down => Movement::Up,
+            Movement::Left => Movement::Right,
+            Movement::Right => Movement::Left,
+        }
+    }
+
     fn next_position(&self, pos: &Position) -> Position {
         use Movement::*;
         match self {
@@ -175,6 +368,61 @@ mod tests {
         assert_eq!(part2(&instructions), "5DB3");
     }
 
+    #[test]
+    fn test_custom_keypad() {
+        let keypad = Keypad::parse("ab\n c\n", 'a').unwrap();
+        let instructions = Instructions::from(&["RD", "L", "UUL"]);
+        assert_eq!(keypad.bathroom_code(&instructions), "cca");
+
+        let keypad = Keypad::new(&["€", "ñ"], 'ñ').unwrap();
+        assert_eq!(keypad.bathroom_code(&Instructions::from(&["U"])), "€");
+    }
+
+    #[test]
+    fn test_invalid_keypads() {
+        assert_eq!(
+            Keypad::parse("12\n21", '1').unwrap_err(),
+            KeypadError::DuplicateKey('2')
+        );
+        assert_eq!(
+            Keypad::parse("123", '5').unwrap_err(),
+            KeypadError::MissingStart('5')
+        );
+        assert_eq!(Keypad::parse(" \n  ", ' ').unwrap_err(), KeypadError::Empty);
+    }
+
+    #[test]
+    fn test_instructions_for() {
+        let keypad = Keypad::new(&KEYPAD1, '5').unwrap();
+        let lines = keypad.instructions_for("1985").unwrap();
+        assert_eq!(lines[0], vec!["LU", "UL"]);
+        assert_eq!(
+            lines[1],
+            vec!["DDRR", "DRDR", "DRRD", "RDDR", "RDRD", "RRDD"]
+        );
+        assert_eq!(lines[2], vec!["L"]);
+        assert_eq!(lines[3], vec!["U"]);
+        assert_eq!(
+            keypad.instructions_for("55").unwrap(),
+            vec![vec![String::new()], vec![String::new()]]
+        );
+        assert_eq!(
+            keypad.instructions_for("0"),
+            Err(KeypadError::UnknownKey('0'))
+        );
+
+        let keypad = Keypad::new(&KEYPAD2, '5').unwrap();
+        let lines = keypad.instructions_for("5DB3").unwrap();
+        assert_eq!(lines[1], vec!["RDRD", "RRDD"]);
+        for alternatives in &lines {
+            for line in alternatives {
+                assert_eq!(line.len(), alternatives[0].len());
+            }
+        }
+        let first: Vec<&str> = lines.iter().map(|l| l[0].as_str()).collect();
+        assert_eq!(keypad.bathroom_code(&Instructions::from(&first)), "5DB3");
+    }
+
     #[test]
     fn test_part2() {
         assert_eq!(part2(&INPUT), "A6B35");
@@ -1,56 +1,285 @@
 use once_cell::sync::Lazy;
-use std::str::FromStr;
+use std::collections::{BTreeMap, VecDeque};
+use std::io::BufRead;
 
-static INPUT: Lazy<Vec<Triplet>> =
-    Lazy::new(|| common::read_file_as_elements("data/day03.txt").unwrap());
+static INPUT: Lazy<String> = Lazy::new(|| common::read_file_as_string("data/day03.txt").unwrap());
 
 fn main() {
     println!("Part1 : {}", part1(&INPUT));
     println!("Part1 : {}", part2(&INPUT));
+    if std::env::args().any(|arg| arg == "--classify") {
+        for (label, reader) in [
+            ("Rows", TriangleReader::row_major(INPUT.as_bytes())),
+            (
+                "Columns",
+                TriangleReader::column_major(INPUT.as_bytes(), 3).unwrap(),
+            ),
+        ] {
+            println!("{}:", label);
+            for (kind, count) in classify(reader).unwrap() {
+                println!("  {:?}: {}", kind, count);
+            }
+        }
+    }
+}
+
+fn part1(input: &str) -> usize {
+    count_triangles(TriangleReader::row_major(input.as_bytes())).unwrap()
 }
 
-fn part1(input: &[Triplet]) -> usize {
-    input.iter().filter(|t| t.is_triangle()).count()
+fn part2(input: &str) -> usize {
+    count_triangles(TriangleReader::column_major(input.as_bytes(), 3).unwrap()).unwrap()
+}
+
+fn count_triangles<R: BufRead>(reader: TriangleReader<R>) -> Result<usize, InputError> {
+    let mut count = 0;
+    for triplet in reader {
+        if triplet?.is_triangle() {
+            count += 1;
+        }
+    }
+    Ok(count)
 }
 
-fn part2(input: &[Triplet]) -> usize {
-    let transposed = transpose_group_of_three(&input);
-    part1(&transposed)
+fn classify<R: BufRead>(reader: TriangleReader<R>) -> Result<BTreeMap<Kind, usize>, InputError> {
+    let mut counts = BTreeMap::new();
+    for triplet in reader {
+        *counts.entry(triplet?.kind()).or_insert(0) += 1;
+    }
+    Ok(counts)
+}
+
+#[derive(Debug, PartialEq)]
+enum InputError {
+    Io(String),
+    InvalidNumber {
+        line: usize,
+        token: String,
+    },
+    RaggedRow {
+        line: usize,
+        expected: usize,
+        found: usize,
+    },
+    IndivisibleGroup {
+        line: usize,
+        values: usize,
+    },
+    IncompleteGroup {
+        rows: usize,
+        height: usize,
+    },
+    InvalidHeight(usize),
+}
+
+impl std::fmt::Display for InputError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            InputError::Io(e) => write!(f, "Read error: {}", e),
+            InputError::InvalidNumber { line, token } => {
+                write!(f, "Line {}: invalid number {:?}", line, token)
+            }
+            InputError::RaggedRow {
+                line,
+                expected,
+                found,
+            } => {
+                write!(
+                    f,
+                    "Line {}: expected {} values, found {}",
+                    line, expected, found
+                )
+            }
+            InputError::IndivisibleGroup { line, values } => {
+                write!(
+                    f,
+                    "Line {}: {} values cannot be split into sides of three",
+                    line, values
+                )
+            }
+            InputError::IncompleteGroup { rows, height } => {
+                write!(f, "Trailing group has {} of {} rows", rows, height)
+            }
+            InputError::InvalidHeight(height) => {
+                write!(
+                    f,
+                    "Group height {} is not a positive multiple of three",
+                    height
+                )
+            }
+        }
+    }
+}
+
+impl std::error::Error for InputError {}
+
+// Reads rows of whitespace-separated side lengths, one row at a time, and
+// yields triplets either along the rows or down the columns of groups of
+// `height` rows. All rows must have the same number of values.
+struct TriangleReader<R: BufRead> {
+    lines: std::io::Lines<R>,
+    line: usize,
+    width: Option<usize>,
+    height: Option<usize>,
+    pending: VecDeque<Triplet>,
+    failed: bool,
+}
+
+impl<R: BufRead> TriangleReader<R> {
+    fn row_major(reader: R) -> Self {
+        Self::new(reader, None)
+    }
+
+    // Each column of a group is cut into triplets, so the height must be a
+    // multiple of three for no triplet to span two columns.
+    fn column_major(reader: R, height: usize) -> Result<Self, InputError> {
+        if height == 0 || !height.is_multiple_of(3) {
+            return Err(InputError::InvalidHeight(height));
+        }
+        Ok(Self::new(reader, Some(height)))
+    }
+
+    fn new(reader: R, height: Option<usize>) -> Self {
+        Self {
+            lines: reader.lines(),
+            line: 0,
+            width: None,
+            height,
+            pending: VecDeque::new(),
+            failed: false,
+        }
+    }
+
+    fn next_row(&mut self) -> Option<Result<Vec<u32>, InputError>> {
+        loop {
+            let text = match self.lines.next()? {
+                Ok(text) => text,
+                Err(e) => return Some(Err(InputError::Io(e.to_string()))),
+            };
+            self.line += 1;
+            if text.trim().is_empty() {
+                continue;
+            }
+            return Some(self.parse_row(&text));
+        }
+    }
+
+    fn parse_row(&mut self, text: &str) -> Result<Vec<u32>, InputError> {
+        let row = text
+            .split_whitespace()
+            .map(|token| {
+                token.parse().map_err(|_| InputError::InvalidNumber {
+                    line: self.line,
+                    token: token.to_string(),
+                })
+            })
+            .collect::<Result<Vec<u32>, _>>()?;
+        let expected = *self.width.get_or_insert(row.len());
+        if row.len() != expected {
+            return Err(InputError::RaggedRow {
+                line: self.line,
+                expected,
+                found: row.len(),
+            });
+        }
+        Ok(row)
+    }
+
+    // Fills `pending` with the triplets of the next row (or group of rows).
+    fn refill(&mut self) -> Option<Result<(), InputError>> {
+        let values = match self.height {
+            None => match self.next_row()? {
+                Ok(row) => row,
+                Err(e) => return Some(Err(e)),
+            },
+            Some(height) => {
+                let mut group = Vec::with_capacity(height);
+                while group.len() < height {
+                    match self.next_row() {
+                        Some(Ok(row)) => group.push(row),
+                        Some(Err(e)) => return Some(Err(e)),
+                        None if group.is_empty() => return None,
+                        None => {
+                            return Some(Err(InputError::IncompleteGroup {
+                                rows: group.len(),
+                                height,
+                            }));
+                        }
+                    }
+                }
+                let width = group[0].len();
+                (0..width)
+                    .flat_map(|x| group.iter().map(move |row| row[x]))
+                    .collect()
+            }
+        };
+        if values.len() % 3 != 0 {
+            return Some(Err(InputError::IndivisibleGroup {
+                line: self.line,
+                values: values.len(),
+            }));
+        }
+        self.pending
+            .extend(values.chunks(3).map(|c| Triplet(c[0], c[1], c[2])));
+        Some(Ok(()))
+    }
 }
 
-fn transpose_group_of_three(input: &[Triplet]) -> Vec<Triplet> {
-    let mut result = Vec::with_capacity(input.len());
-    for chunk in input.chunks(3) {
-        if chunk.len() == 3 {
-            let (a, b, c) = (&chunk[0], &chunk[1], &chunk[2]);
-            result.push(Triplet(a.0, b.0, c.0));
-            result.push(Triplet(a.1, b.1, c.1));
-            result.push(Triplet(a.2, b.2, c.2));
+impl<R: BufRead> Iterator for TriangleReader<R> {
+    type Item = Result<Triplet, InputError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while self.pending.is_empty() {
+            if self.failed {
+                return None;
+            }
+            if let Err(e) = self.refill()? {
+                self.failed = true;
+                return Some(Err(e));
+            }
         }
+        self.pending.pop_front().map(Ok)
     }
-    result
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum Kind {
+    NotTriangle,
+    Degenerate,
+    Equilateral,
+    Isosceles,
+    Right,
+    Scalene,
+}
+
+#[derive(Debug, PartialEq)]
 struct Triplet(u32, u32, u32);
 
 impl Triplet {
     fn is_triangle(&self) -> bool {
-        self.0 + self.1 > self.2 && self.1 + self.2 > self.0 && self.0 + self.2 > self.1
+        let (a, b, c) = (self.0 as u64, self.1 as u64, self.2 as u64);
+        a + b > c && b + c > a && a + c > b
     }
-}
-
-impl FromStr for Triplet {
-    type Err = String;
 
-    fn from_str(s: &str) -> Result<Self, Self::Err> {
-        //  775  785  361
-        let mut parts = s.split_whitespace();
-        Ok(Self(
-            parts.next().unwrap().parse().unwrap(),
-            parts.next().unwrap().parse().unwrap(),
-            parts.next().unwrap().parse().unwrap(),
-        ))
+    // Integer right triangles are never isosceles, so `Right` only narrows `Scalene`.
+    fn kind(&self) -> Kind {
+        // Squares of u32 sides add up past u64::MAX.
+        let mut sides = [self.0 as u128, self.1 as u128, self.2 as u128];
+        sides.sort_unstable();
+        let [a, b, c] = sides;
+        if a + b < c {
+            Kind::NotTriangle
+        } else if a + b == c {
+            Kind::Degenerate
+        } else if a == c {
+            Kind::Equilateral
+        } else if a == b || b == c {
+            Kind::Isosceles
+        } else if a * a + b * b == c * c {
+            Kind::Right
+        } else {
+            Kind::Scalene
+        }
     }
 }
 
@@ -58,6 +287,93 @@ impl FromStr for Triplet {
 mod tests {
     use super::*;
 
+    const EXAMPLE: &str = "101 301 501
+102 302 502
+103 303 503
+201 401 601
+202 402 602
+203 403 603";
+
+    #[test]
+    fn test_example_part2() {
+        let triplets: Vec<Triplet> = TriangleReader::column_major(EXAMPLE.as_bytes(), 3)
+            .unwrap()
+            .collect::<Result<_, _>>()
+            .unwrap();
+        assert_eq!(triplets[0], Triplet(101, 102, 103));
+        assert_eq!(triplets[5], Triplet(601, 602, 603));
+        assert_eq!(part2(EXAMPLE), 6);
+        assert_eq!(part1("5 10 25"), 0);
+        assert_eq!(part1("4294967295 4294967295 4294967295"), 1);
+    }
+
+    #[test]
+    fn test_group_heights() {
+        let reader = TriangleReader::column_major(EXAMPLE.as_bytes(), 6).unwrap();
+        let triplets: Vec<Triplet> = reader.collect::<Result<_, _>>().unwrap();
+        assert_eq!(triplets.len(), 6);
+        assert_eq!(triplets[1], Triplet(201, 202, 203));
+
+        for height in [0, 2, 4] {
+            assert_eq!(
+                TriangleReader::column_major("3 4 5\n6 6 6".as_bytes(), height).err(),
+                Some(InputError::InvalidHeight(height))
+            );
+        }
+    }
+
+    #[test]
+    fn test_errors() {
+        let errors =
+            |reader: TriangleReader<&[u8]>| reader.filter_map(Result::err).collect::<Vec<_>>();
+        let rows = |input: &'static str| TriangleReader::row_major(input.as_bytes());
+        assert_eq!(
+            errors(
+                TriangleReader::column_major("1 2 3\n4 5 6\n7 8 9\n1 2 3".as_bytes(), 3).unwrap()
+            ),
+            vec![InputError::IncompleteGroup { rows: 1, height: 3 }]
+        );
+        assert_eq!(
+            errors(rows("1 2 3\n4 x 6")),
+            vec![InputError::InvalidNumber {
+                line: 2,
+                token: "x".to_string()
+            }]
+        );
+        assert_eq!(
+            errors(rows("1 2 3\n4 5")),
+            vec![InputError::RaggedRow {
+                line: 2,
+                expected: 3,
+                found: 2
+            }]
+        );
+        assert_eq!(
+            errors(rows("1 2\n3 4")),
+            vec![InputError::IndivisibleGroup { line: 1, values: 2 }]
+        );
+    }
+
+    #[test]
+    fn test_classify() {
+        let input = "3 4 5\n2 2 2\n2 2 3\n4 5 6\n1 2 3\n1 2 5";
+        let counts = classify(TriangleReader::row_major(input.as_bytes())).unwrap();
+        let expected = BTreeMap::from([
+            (Kind::NotTriangle, 1),
+            (Kind::Degenerate, 1),
+            (Kind::Equilateral, 1),
+            (Kind::Isosceles, 1),
+            (Kind::Right, 1),
+            (Kind::Scalene, 1),
+        ]);
+        assert_eq!(counts, expected);
+
+        let input = "4294967293 4294967294 4294967295\n3000000000 4000000000 3000000000";
+        let counts = classify(TriangleReader::row_major(input.as_bytes())).unwrap();
+        let expected = BTreeMap::from([(Kind::Isosceles, 1), (Kind::Scalene, 1)]);
+        assert_eq!(counts, expected);
+    }
+
     #[test]
     fn test_part1() {
         assert_eq!(part1(&INPUT), 1032);
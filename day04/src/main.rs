@@ -7,9 +7,41 @@ static INPUT: Lazy<Vec<Room>> =
     Lazy::new(|| common::read_file_as_elements("data/day04.txt").unwrap());
 
 fn main() {
-    println!("Part 1 {}", part1(&INPUT));
-    //explore_part2(&input);
-    println!("Part 2 {}", part2(&INPUT));
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let value = |name: &str| {
+        args.iter()
+            .position(|arg| arg == name)
+            .map(|i| args.get(i + 1).expect("Missing option value"))
+    };
+    if let Some(count) = value("--generate") {
+        let seed = value("--seed").map_or(2016, |s| s.parse().unwrap());
+        let decoys = value("--decoys").map_or(0.25, |s| s.parse().unwrap());
+        let mut generator = RoomGenerator::new(seed);
+        for _ in 0..count.parse().unwrap() {
+            println!("{}", generator.next_room(decoys));
+        }
+        return;
+    }
+    let index = RoomIndex::new(&INPUT);
+    if let Some(text) = value("--search") {
+        print_rooms(index.containing(text));
+    } else if let Some(pattern) = value("--regex") {
+        let re = Regex::new(pattern).expect("Invalid regex");
+        print_rooms(index.matching(&re));
+    } else if args.iter().any(|arg| arg == "--list") {
+        print_rooms(index.by_sector());
+    } else if args.iter().any(|arg| arg == "--json") {
+        println!("{}", to_json(&INPUT));
+    } else {
+        println!("Part 1 {}", part1(&INPUT));
+        println!("Part 2 {}", part2(&INPUT));
+    }
+}
+
+fn print_rooms<'a>(rooms: impl Iterator<Item = &'a DecryptedRoom<'a>>) {
+    for room in rooms {
+        println!("{:4} {}", room.room.sector_id, room.decrypted);
+    }
 }
 
 fn part1(input: &[Room]) -> u32 {
@@ -21,19 +53,71 @@ fn part1(input: &[Room]) -> u32 {
 }
 
 fn part2(input: &[Room]) -> u32 {
-    input
-        .iter()
-        .find(|r| r.is_real() && r.descrypt() == "northpole object storage")
+    RoomIndex::new(input)
+        .named("northpole object storage")
         .unwrap()
+        .room
         .sector_id
 }
 
-#[allow(dead_code)]
-fn explore_part2(input: &[Room]) {
-    input
+struct DecryptedRoom<'a> {
+    room: &'a Room,
+    decrypted: String,
+}
+
+// The real rooms with their decrypted names, ordered by sector id.
+struct RoomIndex<'a> {
+    rooms: Vec<DecryptedRoom<'a>>,
+}
+
+impl<'a> RoomIndex<'a> {
+    fn new(rooms: &'a [Room]) -> Self {
+        let mut rooms: Vec<DecryptedRoom> = rooms
+            .iter()
+            .filter(|room| room.is_real())
+            .map(|room| DecryptedRoom {
+                room,
+                decrypted: room.descrypt(),
+            })
+            .collect();
+        rooms.sort_by_key(|r| r.room.sector_id);
+        Self { rooms }
+    }
+
+    fn by_sector(&self) -> impl Iterator<Item = &DecryptedRoom<'a>> {
+        self.rooms.iter()
+    }
+
+    fn containing<'b>(&'b self, text: &'b str) -> impl Iterator<Item = &'b DecryptedRoom<'a>> {
+        self.rooms
+            .iter()
+            .filter(move |r| r.decrypted.contains(text))
+    }
+
+    fn named(&self, name: &str) -> Option<&DecryptedRoom<'a>> {
+        self.rooms.iter().find(|r| r.decrypted == name)
+    }
+
+    fn matching<'b>(&'b self, re: &'b Regex) -> impl Iterator<Item = &'b DecryptedRoom<'a>> {
+        self.rooms.iter().filter(move |r| re.is_match(&r.decrypted))
+    }
+}
+
+// Every room, real or not, in input order.
+fn to_json(rooms: &[Room]) -> String {
+    let rooms: Vec<serde_json::Value> = rooms
         .iter()
-        .filter(|r| r.is_real())
-        .for_each(|r| println!("{}", r.descrypt()));
+        .map(|room| {
+            serde_json::json!({
+                "name": room.name,
+                "sector_id": room.sector_id,
+                "checksum": room.checksum,
+                "is_real": room.is_real(),
+                "decrypted": room.descrypt(),
+            })
+        })
+        .collect();
+    serde_json::to_string_pretty(&rooms).unwrap()
 }
 
 #[derive(Debug)]
@@ -58,6 +142,50 @@ impl Room {
         counts
     }
 
+    // Inverse of `descrypt`: spaces become dashes and letters are shifted back.
+    fn encrypt(plaintext: &str, sector_id: u32) -> Result<Room, String> {
+        // Without any letter there would be nothing to checksum.
+        if !plaintext.chars().any(|c| c.is_ascii_lowercase())
+            || !plaintext
+                .chars()
+                .all(|c| c == ' ' || c.is_ascii_lowercase())
+        {
+            return Err(format!("Invalid plaintext {:?}", plaintext));
+        }
+        let key = 26 - (sector_id % 26) as u8;
+        let name: String = plaintext
+            .chars()
+            .map(|c| {
+                if c == ' ' {
+                    '-'
+                } else {
+                    (((c as u8 - b'a' + key) % 26) + b'a') as char
+                }
+            })
+            .collect();
+        let mut room = Room {
+            name,
+            sector_id,
+            checksum: String::new(),
+        };
+        room.checksum = checksum(room.count_letters());
+        Ok(room)
+    }
+
+    // Same room with a checksum guaranteed not to match.
+    fn decoy(&self) -> Room {
+        let checksum = self
+            .checksum
+            .bytes()
+            .map(|b| ((b - b'a' + 1) % 26 + b'a') as char)
+            .collect();
+        Room {
+            name: self.name.clone(),
+            sector_id: self.sector_id,
+            checksum,
+        }
+    }
+
     fn descrypt(&self) -> String {
         let key = (self.sector_id % 26) as u8;
         self.name
@@ -80,14 +208,27 @@ fn checksum(counters: HashMap<char, u32>) -> String {
     entries.into_iter().take(5).map(|e| e.0).collect()
 }
 
+impl std::fmt::Display for Room {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}-{}[{}]", self.name, self.sector_id, self.checksum)
+    }
+}
+
 impl FromStr for Room {
     type Err = String;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
         let re = Regex::new(r"^([a-z-]+)-(\d+)\[([a-z]+)]$").unwrap();
-        let caps = re.captures(s).unwrap();
+        let caps = re
+            .captures(s)
+            .ok_or_else(|| format!("Invalid room {:?}", s))?;
         let name = caps.get(1).unwrap().as_str();
-        let sector_id = caps.get(2).unwrap().as_str().parse::<u32>().unwrap();
+        let sector_id = caps
+            .get(2)
+            .unwrap()
+            .as_str()
+            .parse::<u32>()
+            .map_err(|e| format!("Invalid sector id in {:?}: {}", s, e))?;
         let checksum = caps.get(3).unwrap().as_str();
         Ok(Room {
             name: name.to_string(),
@@ -97,6 +238,54 @@ impl FromStr for Room {
     }
 }
 
+// Deterministic source of synthetic rooms (xorshift64*), so a seed always
+// produces the same corpus.
+struct RoomGenerator {
+    state: u64,
+}
+
+impl RoomGenerator {
+    fn new(seed: u64) -> Self {
+        Self { state: seed.max(1) }
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        self.state ^= self.state >> 12;
+        self.state ^= self.state << 25;
+        self.state ^= self.state >> 27;
+        self.state.wrapping_mul(0x2545F4914F6CDD1D)
+    }
+
+    fn below(&mut self, n: u64) -> u64 {
+        self.next_u64() % n
+    }
+
+    fn plaintext(&mut self) -> String {
+        let words = 1 + self.below(5);
+        (0..words)
+            .map(|_| {
+                let length = 3 + self.below(8);
+                (0..length)
+                    .map(|_| (b'a' + self.below(26) as u8) as char)
+                    .collect::<String>()
+            })
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+
+    // A room with a valid checksum, or a decoy with probability `decoys`.
+    fn next_room(&mut self, decoys: f64) -> Room {
+        let plaintext = self.plaintext();
+        let sector_id = 100 + self.below(900) as u32;
+        let room = Room::encrypt(&plaintext, sector_id).unwrap();
+        if (self.next_u64() as f64 / u64::MAX as f64) < decoys {
+            room.decoy()
+        } else {
+            room
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -113,6 +302,103 @@ mod tests {
         assert!(!room4.is_real());
     }
 
+    #[test]
+    fn test_room_index() {
+        let rooms: Vec<Room> = [
+            "qzmt-zixmtkozy-ivhz-343[zimth]",
+            "aaaaa-bbb-z-y-x-123[abxyz]",
+            "totally-real-room-200[decoy]",
+        ]
+        .iter()
+        .map(|s| s.parse().unwrap())
+        .collect();
+        let index = RoomIndex::new(&rooms);
+        let sectors: Vec<u32> = index.by_sector().map(|r| r.room.sector_id).collect();
+        assert_eq!(sectors, vec![123, 343]);
+
+        let found: Vec<&str> = index
+            .containing("encrypted")
+            .map(|r| r.decrypted.as_str())
+            .collect();
+        assert_eq!(found, vec!["very encrypted name"]);
+
+        let re = Regex::new(r"^[a-z]+ [a-z]+ name$").unwrap();
+        assert_eq!(index.matching(&re).count(), 1);
+        assert_eq!(index.containing("decoy").count(), 0);
+        assert_eq!(
+            index.named("very encrypted name").unwrap().room.sector_id,
+            343
+        );
+        assert!(index.named("very encrypted").is_none());
+
+        let rooms = vec![
+            Room::encrypt("northpole object storage annex", 100).unwrap(),
+            Room::encrypt("northpole object storage", 500).unwrap(),
+        ];
+        assert_eq!(part2(&rooms), 500);
+    }
+
+    #[test]
+    fn test_to_json() {
+        let rooms = vec![Room::from_str("totally-real-room-200[decoy]").unwrap()];
+        let json: serde_json::Value = serde_json::from_str(&to_json(&rooms)).unwrap();
+        assert_eq!(
+            json,
+            serde_json::json!([{
+                "name": "totally-real-room",
+                "sector_id": 200,
+                "checksum": "decoy",
+                "is_real": false,
+                "decrypted": "lglsddq jwsd jgge",
+            }])
+        );
+    }
+
+    #[test]
+    fn test_encrypt() {
+        let room = Room::encrypt("very encrypted name", 343).unwrap();
+        assert_eq!(room.to_string(), "qzmt-zixmtkozy-ivhz-343[zimth]");
+        assert!(room.is_real());
+        assert!(!room.decoy().is_real());
+        assert!(Room::encrypt("Not Lowercase", 1).is_err());
+        assert!(Room::encrypt("", 1).is_err());
+        assert!(Room::encrypt("   ", 1).is_err());
+    }
+
+    #[test]
+    fn test_generator() {
+        let mut generator = RoomGenerator::new(7);
+        let lines: Vec<String> = (0..200)
+            .map(|_| generator.next_room(0.5).to_string())
+            .collect();
+        let rooms: Vec<Room> = lines.iter().map(|line| line.parse().unwrap()).collect();
+        let real = rooms.iter().filter(|room| room.is_real()).count();
+        assert!(real > 50 && real < 150);
+        for (line, room) in lines.iter().zip(&rooms) {
+            assert_eq!(&room.to_string(), line);
+            if room.is_real() {
+                let plaintext = room.descrypt();
+                assert_eq!(
+                    Room::encrypt(&plaintext, room.sector_id)
+                        .unwrap()
+                        .to_string(),
+                    *line
+                );
+            }
+        }
+
+        let mut again = RoomGenerator::new(7);
+        assert_eq!(again.next_room(0.5).to_string(), lines[0]);
+        assert!(RoomGenerator::new(1).next_room(0.0).is_real());
+    }
+
+    #[test]
+    fn test_invalid_rooms() {
+        assert!(Room::from_str("").is_err());
+        assert!(Room::from_str("abc-123").is_err());
+        assert!(Room::from_str("abc-99999999999[abc]").is_err());
+    }
+
     #[test]
     fn test_part1() {
         assert_eq!(part1(&INPUT), 158835);
@@ -1,5 +1,4 @@
 use once_cell::sync::Lazy;
-use std::collections::HashMap;
 
 static INPUT: Lazy<Vec<String>> =
     Lazy::new(|| common::read_file_as_lines("data/day06.txt").unwrap());
@@ -7,6 +6,32 @@ static INPUT: Lazy<Vec<String>> =
 fn main() {
     println!("Part 1: {}", part1(&INPUT));
     println!("Part 2: {}", part2(&INPUT));
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if args.iter().any(|arg| arg == "--tables") {
+        let tie = if args.iter().any(|arg| arg == "--first-seen") {
+            TieBreak::FirstSeen
+        } else {
+            TieBreak::Alphabetical
+        };
+        let counter = count(&INPUT);
+        for (i, column) in counter.0.iter().enumerate() {
+            let table: Vec<String> = column
+                .table(tie)
+                .iter()
+                .map(|(c, n)| format!("{}:{}", c, n))
+                .collect();
+            println!("{:2} ({:.2}) {}", i, column.confidence(), table.join(" "));
+        }
+        for (label, most_common) in [("Most", true), ("Least", false)] {
+            let recovered = counter.select(most_common, tie);
+            let message: String = recovered.iter().map(|r| r.ch).collect();
+            let confidences: Vec<String> = recovered
+                .iter()
+                .map(|r| format!("{}x{} ({:.2})", r.ch, r.count, r.confidence))
+                .collect();
+            println!("{}: {} {}", label, message, confidences.join(" "));
+        }
+    }
 }
 
 fn part1<T: AsRef<str>>(input: &[T]) -> String {
@@ -18,46 +43,115 @@ fn part2<T: AsRef<str>>(input: &[T]) -> String {
 }
 
 fn count<T: AsRef<str>>(input: &[T]) -> ColumnCounter {
-    let length = input[0].as_ref().len();
-    let mut counter = ColumnCounter::new(length);
+    let mut counter = ColumnCounter::default();
     for line in input {
         counter.add(line.as_ref());
     }
     counter
 }
 
-struct ColumnCounter(Vec<HashMap<char, usize>>);
+#[derive(Debug, Clone, Copy)]
+enum TieBreak {
+    Alphabetical,
+    FirstSeen,
+}
 
-impl ColumnCounter {
-    fn new(length: usize) -> Self {
-        Self(vec![HashMap::new(); length])
+#[derive(Debug, PartialEq)]
+struct Recovered {
+    ch: char,
+    count: usize,
+    confidence: f64,
+}
+
+// Characters in the order they were first seen in the column, with their counts.
+#[derive(Debug, Default)]
+struct Column {
+    counts: Vec<(char, usize)>,
+    total: usize,
+}
+
+impl Column {
+    fn add(&mut self, c: char) {
+        match self.counts.iter_mut().find(|(seen, _)| *seen == c) {
+            Some((_, n)) => *n += 1,
+            None => self.counts.push((c, 1)),
+        }
+        self.total += 1;
+    }
+
+    // Most frequent first; ties ordered by `tie`.
+    fn table(&self, tie: TieBreak) -> Vec<(char, usize)> {
+        let mut table = self.counts.clone();
+        match tie {
+            TieBreak::Alphabetical => table.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0))),
+            TieBreak::FirstSeen => table.sort_by_key(|&(_, n)| std::cmp::Reverse(n)),
+        }
+        table
     }
 
+    // One minus the normalised Shannon entropy of the column: 1.0 when a single
+    // character appears, 0.0 when all the characters seen are equally frequent.
+    fn confidence(&self) -> f64 {
+        if self.counts.len() < 2 {
+            return 1.0;
+        }
+        let total = self.total as f64;
+        let entropy: f64 = self
+            .counts
+            .iter()
+            .map(|&(_, n)| {
+                let p = n as f64 / total;
+                -p * p.log2()
+            })
+            .sum();
+        1.0 - entropy / (self.counts.len() as f64).log2()
+    }
+}
+
+// Lines may have different lengths; a column only counts the lines reaching it.
+#[derive(Debug, Default)]
+struct ColumnCounter(Vec<Column>);
+
+impl ColumnCounter {
     fn add(&mut self, word: &str) {
         for (i, c) in word.chars().enumerate() {
-            *self.0[i].entry(c).or_insert(0) += 1;
+            if i == self.0.len() {
+                self.0.push(Column::default());
+            }
+            self.0[i].add(c);
         }
     }
 
     fn maxs(&self) -> String {
-        self.select_by(|a, b| a.1.cmp(b.1))
+        self.select(true, TieBreak::Alphabetical)
+            .iter()
+            .map(|r| r.ch)
+            .collect()
     }
 
     fn mins(&self) -> String {
-        self.select_by(|a, b| b.1.cmp(a.1))
+        self.select(false, TieBreak::Alphabetical)
+            .iter()
+            .map(|r| r.ch)
+            .collect()
     }
 
-    fn select_by<F>(&self, cmp: F) -> String
-    where
-        F: Fn(&(&char, &usize), &(&char, &usize)) -> std::cmp::Ordering,
-    {
+    fn select(&self, most_common: bool, tie: TieBreak) -> Vec<Recovered> {
         self.0
             .iter()
-            .map(|map| {
-                map.iter()
-                    .max_by(|a, b| cmp(a, b))
-                    .map(|(&ch, _)| ch)
-                    .unwrap()
+            .map(|column| {
+                let table = column.table(tie);
+                let (ch, count) = if most_common {
+                    table[0]
+                } else {
+                    let least = table.last().unwrap().1;
+                    *table.iter().find(|(_, n)| *n == least).unwrap()
+                };
+                Recovered {
+                    ch,
+                    count,
+                    confidence: column.confidence(),
+                }
             })
             .collect()
     }
@@ -67,6 +161,7 @@ impl ColumnCounter {
 mod tests {
     use super::*;
 
+    #[allow(clippy::redundant_static_lifetimes)]
     static EXAMPLE_INPUT: [&'static str; 16] = [
         "eedadn", "drvtee", "eandsr", "raavrd", "atevrs", "tsrnev", "sdttsa", "rasrtv", "nssdts",
         "ntnada", "svetve", "tesnvt", "vntsnd", "vrdear", "dvrsen", "enarar",
@@ -77,6 +172,39 @@ mod tests {
         assert_eq!(part1(&EXAMPLE_INPUT), "easter");
     }
 
+    #[test]
+    fn test_ties() {
+        let counter = count(&["ba", "ab", "cc"]);
+        assert_eq!(counter.maxs(), "aa");
+        let first_seen: String = counter
+            .select(true, TieBreak::FirstSeen)
+            .iter()
+            .map(|r| r.ch)
+            .collect();
+        assert_eq!(first_seen, "ba");
+        let least: String = counter
+            .select(false, TieBreak::FirstSeen)
+            .iter()
+            .map(|r| r.ch)
+            .collect();
+        assert_eq!(least, "ba");
+    }
+
+    #[test]
+    fn test_ragged_unicode() {
+        let counter = count(&["é", "ñéx", "ñ", "ñéy"]);
+        assert_eq!(counter.maxs(), "ñéx");
+        assert_eq!(
+            counter.0[2].table(TieBreak::Alphabetical),
+            vec![('x', 1), ('y', 1)]
+        );
+        let recovered = counter.select(true, TieBreak::Alphabetical);
+        assert_eq!(recovered[0].count, 3);
+        assert_eq!(recovered[1].confidence, 1.0);
+        assert_eq!(recovered[2].confidence, 0.0);
+        assert!(recovered[0].confidence > 0.0 && recovered[0].confidence < 1.0);
+    }
+
     #[test]
     fn test_part1() {
         assert_eq!(part1(&INPUT), "qrqlznrl");
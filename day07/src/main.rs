@@ -1,15 +1,48 @@
 use once_cell::sync::Lazy;
-use std::collections::HashSet;
 use std::str::FromStr;
 
 static INPUT: Lazy<Vec<IPv7Address>> =
     Lazy::new(|| common::read_file_as_elements("data/day07.txt").unwrap());
 
 fn main() {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if let Some(i) = args.iter().position(|arg| arg == "--explain") {
+        let address = args.get(i + 1).expect("Missing address");
+        match address.parse::<IPv7Address>() {
+            Ok(ip) => explain(&ip),
+            Err(e) => println!("{}", e),
+        }
+        return;
+    }
     println!("Part 1: {}", part1(&INPUT));
     println!("Part 2: {}", part2(&INPUT));
 }
 
+fn explain(ip: &IPv7Address) {
+    let tls = ip.analyze_tls();
+    println!("TLS: {}", tls.supported());
+    for w in &tls.supernet_abbas {
+        println!(
+            "  ABBA {} in supernet segment {} at {}",
+            w.text, w.segment, w.position
+        );
+    }
+    for w in &tls.hypernet_abbas {
+        println!(
+            "  ABBA {} in hypernet segment {} at {}",
+            w.text, w.segment, w.position
+        );
+    }
+    let ssl = ip.analyze_ssl();
+    println!("SSL: {}", ssl.supported());
+    for (aba, bab) in &ssl.pairs {
+        println!(
+            "  ABA {} (segment {} at {}) with BAB {} (segment {} at {})",
+            aba.text, aba.segment, aba.position, bab.text, bab.segment, bab.position
+        );
+    }
+}
+
 fn part1(input: &[IPv7Address]) -> usize {
     input.iter().filter(|ip| ip.supports_tls()).count()
 }
@@ -18,76 +51,203 @@ fn part2(input: &[IPv7Address]) -> usize {
     input.iter().filter(|ip| ip.supports_ssl()).count()
 }
 
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Net {
+    Supernet,
+    Hypernet,
+}
+
+#[derive(Debug, Clone)]
+struct Segment {
+    net: Net,
+    text: String,
+    // Position of the first character of the segment in the address.
+    start: usize,
+}
+
 #[derive(Debug, Clone)]
 struct IPv7Address {
-    supernet: Vec<String>,
-    hypernet: Vec<String>,
+    segments: Vec<Segment>,
 }
 
+// Positions are character offsets in the address.
+#[derive(Debug, PartialEq)]
+enum AddressParseError {
+    NestedBracket { position: usize, open: usize },
+    UnmatchedClose { position: usize },
+    Unclosed { open: usize },
+    EmptyHypernet { open: usize },
+}
+
+impl std::fmt::Display for AddressParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            AddressParseError::NestedBracket { position, open } => write!(
+                f,
+                "Nested '[' at {} inside the bracket opened at {}",
+                position, open
+            ),
+            AddressParseError::UnmatchedClose { position } => {
+                write!(f, "Unmatched ']' at {}", position)
+            }
+            AddressParseError::Unclosed { open } => {
+                write!(f, "Bracket opened at {} is never closed", open)
+            }
+            AddressParseError::EmptyHypernet { open } => {
+                write!(f, "Empty hypernet sequence at {}", open)
+            }
+        }
+    }
+}
+
+impl std::error::Error for AddressParseError {}
+
 impl FromStr for IPv7Address {
-    type Err = String;
+    type Err = AddressParseError;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        let mut supernet = Vec::new();
-        let mut hypernet = Vec::new();
+        let mut segments = Vec::new();
         let mut current = String::new();
-        for c in s.chars() {
-            if c == '[' {
-                if !current.is_empty() {
-                    supernet.push(current);
-                    current = String::new();
+        let mut start = 0;
+        let mut open = None;
+        for (position, c) in s.chars().enumerate() {
+            match (c, open) {
+                ('[', Some(open)) => {
+                    return Err(AddressParseError::NestedBracket { position, open });
                 }
-            } else if c == ']' {
-                if !current.is_empty() {
-                    hypernet.push(current);
-                    current = String::new();
+                ('[', None) => {
+                    if !current.is_empty() {
+                        segments.push(Segment {
+                            net: Net::Supernet,
+                            text: std::mem::take(&mut current),
+                            start,
+                        });
+                    }
+                    open = Some(position);
+                    start = position + 1;
                 }
-            } else {
-                current.push(c);
+                (']', None) => return Err(AddressParseError::UnmatchedClose { position }),
+                (']', Some(open_position)) => {
+                    if current.is_empty() {
+                        return Err(AddressParseError::EmptyHypernet {
+                            open: open_position,
+                        });
+                    }
+                    segments.push(Segment {
+                        net: Net::Hypernet,
+                        text: std::mem::take(&mut current),
+                        start,
+                    });
+                    open = None;
+                    start = position + 1;
+                }
+                _ => current.push(c),
             }
         }
+        if let Some(open) = open {
+            return Err(AddressParseError::Unclosed { open });
+        }
         if !current.is_empty() {
-            supernet.push(current);
+            segments.push(Segment {
+                net: Net::Supernet,
+                text: current,
+                start,
+            });
         }
-        Ok(Self { supernet, hypernet })
+        Ok(Self { segments })
+    }
+}
+
+// A pattern found in the address: `segment` indexes the address segments and
+// `position` is the offset of the pattern in the whole address.
+#[derive(Debug, Clone, PartialEq)]
+struct Witness {
+    segment: usize,
+    position: usize,
+    text: String,
+}
+
+#[derive(Debug)]
+struct TlsAnalysis {
+    supernet_abbas: Vec<Witness>,
+    hypernet_abbas: Vec<Witness>,
+}
+
+impl TlsAnalysis {
+    fn supported(&self) -> bool {
+        !self.supernet_abbas.is_empty() && self.hypernet_abbas.is_empty()
+    }
+}
+
+#[derive(Debug)]
+struct SslAnalysis {
+    pairs: Vec<(Witness, Witness)>,
+}
+
+impl SslAnalysis {
+    fn supported(&self) -> bool {
+        !self.pairs.is_empty()
     }
 }
 
 impl IPv7Address {
     fn supports_tls(&self) -> bool {
-        self.supernet.iter().any(|s| IPv7Address::is_abba(s))
-            && self.hypernet.iter().all(|s| !IPv7Address::is_abba(s))
+        self.analyze_tls().supported()
     }
 
-    fn is_abba(s: &str) -> bool {
-        s.as_bytes()
-            .windows(4)
-            .any(|w| w[0] == w[3] && w[1] == w[2] && w[0] != w[1])
+    fn supports_ssl(&self) -> bool {
+        self.analyze_ssl().supported()
     }
 
-    fn supports_ssl(&self) -> bool {
-        let all_abas_supernet = IPv7Address::all_triplets(&self.supernet);
-        let all_babs_hypernet = IPv7Address::all_triplets(&self.hypernet);
-        let all_babs_supernet = IPv7Address::invert_triplets(&all_abas_supernet);
-        all_babs_supernet.intersection(&all_babs_hypernet).count() > 0
+    fn analyze_tls(&self) -> TlsAnalysis {
+        TlsAnalysis {
+            supernet_abbas: self.witnesses(Net::Supernet, 4, IPv7Address::is_abba),
+            hypernet_abbas: self.witnesses(Net::Hypernet, 4, IPv7Address::is_abba),
+        }
     }
 
-    fn all_triplets(input: &[String]) -> HashSet<(u8, u8, u8)> {
-        input
-            .iter()
-            .flat_map(|s| IPv7Address::get_triplets(s))
-            .collect()
+    fn analyze_ssl(&self) -> SslAnalysis {
+        let abas = self.witnesses(Net::Supernet, 3, IPv7Address::is_aba);
+        let babs = self.witnesses(Net::Hypernet, 3, IPv7Address::is_aba);
+        let mut pairs = Vec::new();
+        for aba in &abas {
+            let a: Vec<char> = aba.text.chars().collect();
+            for bab in &babs {
+                let b: Vec<char> = bab.text.chars().collect();
+                if b[0] == a[1] && b[1] == a[0] {
+                    pairs.push((aba.clone(), bab.clone()));
+                }
+            }
+        }
+        SslAnalysis { pairs }
     }
 
-    fn get_triplets(s: &str) -> impl Iterator<Item = (u8, u8, u8)>  {
-        s.as_bytes()
-            .windows(3)
-            .filter(|w| w[0] == w[2] && w[0] != w[1])
-            .map(|w| (w[0], w[1], w[2]))
+    fn witnesses(&self, net: Net, size: usize, matches: fn(&[char]) -> bool) -> Vec<Witness> {
+        let mut witnesses = Vec::new();
+        for (index, segment) in self.segments.iter().enumerate() {
+            if segment.net != net {
+                continue;
+            }
+            let chars: Vec<char> = segment.text.chars().collect();
+            for (offset, window) in chars.windows(size).enumerate() {
+                if matches(window) {
+                    witnesses.push(Witness {
+                        segment: index,
+                        position: segment.start + offset,
+                        text: window.iter().collect(),
+                    });
+                }
+            }
+        }
+        witnesses
+    }
+
+    fn is_abba(w: &[char]) -> bool {
+        w[0] == w[3] && w[1] == w[2] && w[0] != w[1]
     }
 
-    fn invert_triplets(abas: &HashSet<(u8, u8, u8)>) -> HashSet<(u8, u8, u8)> {
-        abas.iter().map(|ab| (ab.1, ab.0, ab.1)).collect()
+    fn is_aba(w: &[char]) -> bool {
+        w[0] == w[2] && w[0] != w[1]
     }
 }
 
@@ -120,6 +280,61 @@ mod tests {
         assert!(ip("zazbz[bzb]cdb").supports_ssl());
     }
 
+    #[test]
+    fn test_parse_errors() {
+        let err = |s: &str| s.parse::<IPv7Address>().unwrap_err();
+        assert_eq!(
+            err("ab[cd[ef]]gh"),
+            AddressParseError::NestedBracket {
+                position: 5,
+                open: 2
+            }
+        );
+        assert_eq!(
+            err("abc]def"),
+            AddressParseError::UnmatchedClose { position: 3 }
+        );
+        assert_eq!(err("abc[def"), AddressParseError::Unclosed { open: 3 });
+        assert_eq!(
+            err("abc[]def"),
+            AddressParseError::EmptyHypernet { open: 3 }
+        );
+    }
+
+    #[test]
+    fn test_witnesses() {
+        let tls = ip("ioxxoj[asdfgh]zxcvbn").analyze_tls();
+        assert_eq!(
+            tls.supernet_abbas,
+            vec![Witness {
+                segment: 0,
+                position: 1,
+                text: "oxxo".to_string()
+            }]
+        );
+        assert!(tls.hypernet_abbas.is_empty());
+
+        let tls = ip("abcd[bddb]xyyx").analyze_tls();
+        assert_eq!(tls.hypernet_abbas[0].segment, 1);
+        assert_eq!(tls.hypernet_abbas[0].position, 5);
+        assert_eq!(tls.supernet_abbas[0].position, 10);
+
+        let ssl = ip("zazbz[bzb]cdb").analyze_ssl();
+        assert_eq!(ssl.pairs.len(), 1);
+        let (aba, bab) = &ssl.pairs[0];
+        assert_eq!((aba.text.as_str(), aba.position), ("zbz", 2));
+        assert_eq!(
+            (bab.text.as_str(), bab.segment, bab.position),
+            ("bzb", 1, 6)
+        );
+
+        let ssl = ip("éxé[xéx]").analyze_ssl();
+        assert!(ssl.supported());
+        let (aba, bab) = &ssl.pairs[0];
+        assert_eq!((aba.text.as_str(), bab.text.as_str()), ("éxé", "xéx"));
+        assert!(!ip("éxé[éxé]").supports_ssl());
+    }
+
     #[test]
     fn test_part2() {
         assert_eq!(part2(&INPUT), 231);
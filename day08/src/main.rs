@@ -1,33 +1,315 @@
+mod packed;
+
 use once_cell::sync::Lazy;
+use packed::PackedScreen;
 use std::fmt;
+use std::path::Path;
 use std::str::FromStr;
+use std::time::Duration;
 
 static INPUT: Lazy<Vec<Operation>> =
     Lazy::new(|| common::read_file_as_elements("data/day08.txt").unwrap());
 
 fn main() {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let value = |name: &str| {
+        args.iter()
+            .position(|arg| arg == name)
+            .map(|i| args.get(i + 1).expect("Missing option value"))
+    };
+    let delay = Duration::from_millis(value("--delay").map_or(100, |ms| ms.parse().unwrap()));
+    let size = match value("--size").map(String::as_str) {
+        None => (50, 6),
+        Some("auto") => required_size(&INPUT).expect("No screen is large enough"),
+        Some(size) => {
+            let (width, height) = size.split_once('x').expect("Size must be WxH");
+            (width.parse().unwrap(), height.parse().unwrap())
+        }
+    };
+    if args.iter().any(|arg| arg == "--animate") {
+        animate(&INPUT, size, delay).unwrap();
+        return;
+    }
+    if let Some(dir) = value("--pbm") {
+        write_pbm_frames(&INPUT, size, Path::new(dir)).unwrap();
+        return;
+    }
+    if let Some(path) = value("--asciicast") {
+        std::fs::write(path, asciicast(&INPUT, size, delay).unwrap()).unwrap();
+        return;
+    }
+    if let Some(count) = value("--synthetic") {
+        let seed = value("--seed").map_or(2016, |s| s.parse().unwrap());
+        let input = synthetic(count.parse().unwrap(), size, seed);
+        let start = std::time::Instant::now();
+        let mut screen = PackedScreen::new(size.0, size.1);
+        screen.run(&input).unwrap();
+        println!(
+            "Packed: {} lit after {:?}",
+            screen.count_lit(),
+            start.elapsed()
+        );
+        if args.iter().any(|arg| arg == "--compare") {
+            let start = std::time::Instant::now();
+            let screen = final_screen(&input, size).unwrap();
+            println!(
+                "Unpacked: {} lit after {:?}",
+                screen.count_lit(),
+                start.elapsed()
+            );
+        }
+        return;
+    }
+    if args.iter().any(|arg| arg == "--packed") {
+        let mut screen = PackedScreen::new(size.0, size.1);
+        match screen.run(&INPUT) {
+            Ok(()) => println!("{} lit\n{}", screen.count_lit(), screen),
+            Err(e) => fail(e),
+        }
+        return;
+    }
+    if value("--size").is_some() {
+        match final_screen(&INPUT, size) {
+            Ok(screen) => println!("{} lit\n{}", screen.count_lit(), screen),
+            Err(e) => fail(e),
+        }
+        return;
+    }
     println!("Print 1: {}", part1(&INPUT));
-    println!("Print 2:");
-    part2(&INPUT);
+    match part2(&INPUT) {
+        Ok(text) => println!("Print 2: {}", text),
+        Err(e) => {
+            eprintln!("{}", final_screen(&INPUT, (50, 6)).unwrap());
+            fail(e);
+        }
+    }
+}
+
+fn fail(error: impl fmt::Display) -> ! {
+    eprintln!("{}", error);
+    std::process::exit(1);
 }
 
 fn part1(input: &[Operation]) -> usize {
-    let mut screen = Screen::new(50, 6);
-    screen.run(input);
-    screen.count_lit()
+    final_screen(input, (50, 6)).unwrap().count_lit()
 }
 
-fn part2(input: &[Operation]) {
-    let mut screen = Screen::new(50, 6);
-    screen.run(input);
-    println!("{}", screen);
+fn part2(input: &[Operation]) -> Result<String, common::ocr::OcrError> {
+    common::ocr::recognize(&final_screen(input, (50, 6)).unwrap().0)
+}
+
+fn final_screen(input: &[Operation], (width, height): (usize, usize)) -> Result<Screen, StepError> {
+    let mut screen = Screen::new(width, height);
+    screen.run(input)?;
+    Ok(screen)
+}
+
+// Smallest screen on which every operation is valid, or None when its size
+// would not fit in a usize.
+fn required_size(input: &[Operation]) -> Option<(usize, usize)> {
+    use Operation::*;
+    input.iter().try_fold((1, 1), |(w, h), op| match *op {
+        Clear => Some((w, h)),
+        Rect { width, height } => Some((w.max(width), h.max(height))),
+        RotateRow { y, .. } => Some((w, h.max(y.checked_add(1)?))),
+        RotateColumn { x, .. } => Some((w.max(x.checked_add(1)?), h)),
+        InvertRect {
+            x,
+            y,
+            width,
+            height,
+        }
+        | RotateRect {
+            x,
+            y,
+            width,
+            height,
+            ..
+        } => Some((w.max(x.checked_add(width)?), h.max(y.checked_add(height)?))),
+    })
+}
+
+// The screen before any operation, then after each of them.
+fn frames(input: &[Operation], (width, height): (usize, usize)) -> Result<Vec<Screen>, StepError> {
+    let mut screen = Screen::new(width, height);
+    let mut frames = vec![screen.clone()];
+    for (step, op) in input.iter().enumerate() {
+        screen
+            .apply(op)
+            .map_err(|error| StepError { step, error })?;
+        frames.push(screen.clone());
+    }
+    Ok(frames)
+}
+
+fn caption(input: &[Operation], step: usize) -> String {
+    match step {
+        0 => format!("Step 0/{}", input.len()),
+        _ => format!("Step {}/{}: {}", step, input.len(), input[step - 1]),
+    }
+}
+
+fn animate(input: &[Operation], size: (usize, usize), delay: Duration) -> Result<(), StepError> {
+    for (step, screen) in frames(input, size)?.iter().enumerate() {
+        print!("\x1b[2J\x1b[H{}\n{}", caption(input, step), screen);
+        std::thread::sleep(delay);
+    }
+    Ok(())
+}
+
+fn write_pbm_frames(
+    input: &[Operation],
+    size: (usize, usize),
+    dir: &Path,
+) -> Result<(), Box<dyn std::error::Error>> {
+    std::fs::create_dir_all(dir)?;
+    for (step, screen) in frames(input, size)?.iter().enumerate() {
+        std::fs::write(dir.join(format!("frame-{:04}.pbm", step)), screen.to_pbm())?;
+    }
+    Ok(())
 }
 
-#[derive(Debug, Clone)]
+// asciicast v2: a JSON header line followed by one `[time, "o", data]` event
+// line per frame, each one clearing the terminal before drawing.
+fn asciicast(
+    input: &[Operation],
+    size: (usize, usize),
+    delay: Duration,
+) -> Result<String, StepError> {
+    let frames = frames(input, size)?;
+    let captions: Vec<String> = (0..frames.len()).map(|step| caption(input, step)).collect();
+    let width = captions
+        .iter()
+        .map(String::len)
+        .chain([size.0])
+        .max()
+        .unwrap();
+    let header = serde_json::json!({
+        "version": 2,
+        "width": width,
+        "height": size.1 + 1,
+        "title": "day08",
+    });
+    let mut cast = format!("{}\n", header);
+    for (step, (screen, caption)) in frames.iter().zip(&captions).enumerate() {
+        // Without the screen's final newline the cursor stays on the last
+        // of the `height` rows and the frame never scrolls.
+        let rows = screen.to_string();
+        let rows = rows.trim_end_matches('\n');
+        let data = format!("\x1b[2J\x1b[H{}\n{}", caption, rows).replace('\n', "\r\n");
+        let time = (delay * step as u32).as_secs_f64();
+        cast.push_str(&format!("{}\n", serde_json::json!([time, "o", data])));
+    }
+    Ok(cast)
+}
+
+// Random operations that are all valid on a screen of the given size
+// (xorshift64*, so a seed always produces the same sequence).
+fn synthetic(count: usize, (width, height): (usize, usize), seed: u64) -> Vec<Operation> {
+    let mut state = seed.max(1);
+    let mut below = |n: usize| {
+        state ^= state >> 12;
+        state ^= state << 25;
+        state ^= state >> 27;
+        (state.wrapping_mul(0x2545F4914F6CDD1D) % n as u64) as usize
+    };
+    (0..count)
+        .map(|_| match below(20) {
+            0 => Operation::Clear,
+            1..=4 => Operation::Rect {
+                width: 1 + below(width),
+                height: 1 + below(height),
+            },
+            5..=10 => Operation::RotateRow {
+                y: below(height),
+                by: below(2 * width),
+            },
+            11..=16 => Operation::RotateColumn {
+                x: below(width),
+                by: below(2 * height),
+            },
+            17 | 18 => {
+                let (x, y) = (below(width), below(height));
+                Operation::InvertRect {
+                    x,
+                    y,
+                    width: 1 + below(width - x),
+                    height: 1 + below(height - y),
+                }
+            }
+            _ => {
+                let (x, y) = (below(width), below(height));
+                let side = 1 + below((width - x).min(height - y).min(64));
+                Operation::RotateRect {
+                    x,
+                    y,
+                    width: side,
+                    height: side,
+                    by: below(4),
+                }
+            }
+        })
+        .collect()
+}
+
+#[derive(Debug, Clone, PartialEq)]
 enum Operation {
-    Rect { width: usize, height: usize },
-    RotateRow { y: usize, by: usize },
-    RotateColumn { x: usize, by: usize },
+    Clear,
+    Rect {
+        width: usize,
+        height: usize,
+    },
+    RotateRow {
+        y: usize,
+        by: usize,
+    },
+    RotateColumn {
+        x: usize,
+        by: usize,
+    },
+    InvertRect {
+        x: usize,
+        y: usize,
+        width: usize,
+        height: usize,
+    },
+    // Turns the rectangle a quarter turn clockwise `by` times.
+    RotateRect {
+        x: usize,
+        y: usize,
+        width: usize,
+        height: usize,
+        by: usize,
+    },
+}
+
+impl fmt::Display for Operation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        use Operation::*;
+        match self {
+            Clear => write!(f, "clear"),
+            Rect { width, height } => write!(f, "rect {}x{}", width, height),
+            RotateRow { y, by } => write!(f, "rotate row y={} by {}", y, by),
+            RotateColumn { x, by } => write!(f, "rotate column x={} by {}", x, by),
+            InvertRect {
+                x,
+                y,
+                width,
+                height,
+            } => write!(f, "invert rect {}x{} at {},{}", width, height, x, y),
+            RotateRect {
+                x,
+                y,
+                width,
+                height,
+                by,
+            } => write!(
+                f,
+                "rotate rect {}x{} at {},{} by {}",
+                width, height, x, y, by
+            ),
+        }
+    }
 }
 
 impl FromStr for Operation {
@@ -37,37 +319,219 @@ impl FromStr for Operation {
         use Operation::*;
 
         static RECT_REGEX: Lazy<regex::Regex> =
-            Lazy::new(|| regex::Regex::new(r"rect (\d+)x(\d+)").unwrap());
+            Lazy::new(|| regex::Regex::new(r"^rect (\d+)x(\d+)$").unwrap());
         static ROTATE_ROW_REGEX: Lazy<regex::Regex> =
-            Lazy::new(|| regex::Regex::new(r"rotate row y=(\d+) by (\d+)").unwrap());
+            Lazy::new(|| regex::Regex::new(r"^rotate row y=(\d+) by (\d+)$").unwrap());
         static ROTATE_COLUMN_REGEX: Lazy<regex::Regex> =
-            Lazy::new(|| regex::Regex::new(r"rotate column x=(\d+) by (\d+)").unwrap());
-
-        if let Some(captures) = RECT_REGEX.captures(s) {
-            let width = captures.get(1).unwrap().as_str().parse::<usize>().unwrap();
-            let height = captures.get(2).unwrap().as_str().parse::<usize>().unwrap();
-            return Ok(Rect { width, height });
-        } else if let Some(captures) = ROTATE_ROW_REGEX.captures(s) {
-            let y = captures.get(1).unwrap().as_str().parse::<usize>().unwrap();
-            let by = captures.get(2).unwrap().as_str().parse::<usize>().unwrap();
-            return Ok(RotateRow { y, by });
-        } else if let Some(captures) = ROTATE_COLUMN_REGEX.captures(s) {
-            let x = captures.get(1).unwrap().as_str().parse::<usize>().unwrap();
-            let by = captures.get(2).unwrap().as_str().parse::<usize>().unwrap();
-            return Ok(RotateColumn { x, by });
+            Lazy::new(|| regex::Regex::new(r"^rotate column x=(\d+) by (\d+)$").unwrap());
+        static INVERT_RECT_REGEX: Lazy<regex::Regex> = Lazy::new(|| {
+            regex::Regex::new(r"^invert rect (\d+)x(\d+)(?: at (\d+),(\d+))?$").unwrap()
+        });
+        static ROTATE_RECT_REGEX: Lazy<regex::Regex> = Lazy::new(|| {
+            regex::Regex::new(r"^rotate rect (\d+)x(\d+)(?: at (\d+),(\d+))? by (\d+)$").unwrap()
+        });
+
+        let s = s.trim();
+        let number = |captures: &regex::Captures, i: usize| match captures.get(i) {
+            None => Ok(0),
+            Some(m) => m
+                .as_str()
+                .parse::<usize>()
+                .map_err(|_| format!("Number too large: {}", m.as_str())),
+        };
+        if s == "clear" {
+            Ok(Clear)
+        } else if let Some(c) = RECT_REGEX.captures(s) {
+            let width = number(&c, 1)?;
+            let height = number(&c, 2)?;
+            Ok(Rect { width, height })
+        } else if let Some(c) = ROTATE_ROW_REGEX.captures(s) {
+            let y = number(&c, 1)?;
+            let by = number(&c, 2)?;
+            Ok(RotateRow { y, by })
+        } else if let Some(c) = ROTATE_COLUMN_REGEX.captures(s) {
+            let x = number(&c, 1)?;
+            let by = number(&c, 2)?;
+            Ok(RotateColumn { x, by })
+        } else if let Some(c) = INVERT_RECT_REGEX.captures(s) {
+            Ok(InvertRect {
+                width: number(&c, 1)?,
+                height: number(&c, 2)?,
+                x: number(&c, 3)?,
+                y: number(&c, 4)?,
+            })
+        } else if let Some(c) = ROTATE_RECT_REGEX.captures(s) {
+            Ok(RotateRect {
+                width: number(&c, 1)?,
+                height: number(&c, 2)?,
+                x: number(&c, 3)?,
+                y: number(&c, 4)?,
+                by: number(&c, 5)?,
+            })
         } else {
-            return Err("Invalid operation".to_string());
+            Err(format!("Invalid operation: {}", s))
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+enum ScreenError {
+    RowOutOfBounds {
+        y: usize,
+        height: usize,
+    },
+    ColumnOutOfBounds {
+        x: usize,
+        width: usize,
+    },
+    RectOutOfBounds {
+        right: usize,
+        bottom: usize,
+        width: usize,
+        height: usize,
+    },
+    // Odd quarter turns only fit back in place when the rectangle is square.
+    NotSquare {
+        width: usize,
+        height: usize,
+    },
+}
+
+impl fmt::Display for ScreenError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ScreenError::RowOutOfBounds { y, height } => {
+                write!(f, "Row {} is outside a screen {} pixels high", y, height)
+            }
+            ScreenError::ColumnOutOfBounds { x, width } => {
+                write!(f, "Column {} is outside a screen {} pixels wide", x, width)
+            }
+            ScreenError::RectOutOfBounds {
+                right,
+                bottom,
+                width,
+                height,
+            } => write!(
+                f,
+                "Rectangle reaching {},{} does not fit a {}x{} screen",
+                right, bottom, width, height
+            ),
+            ScreenError::NotSquare { width, height } => {
+                write!(
+                    f,
+                    "Cannot turn a {}x{} rectangle a quarter turn",
+                    width, height
+                )
+            }
+        }
+    }
+}
+
+impl std::error::Error for ScreenError {}
+
+// An operation that is invalid on the screen; `step` counts from 0.
+#[derive(Debug, PartialEq)]
+struct StepError {
+    step: usize,
+    error: ScreenError,
+}
+
+impl fmt::Display for StepError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Operation {}: {}", self.step + 1, self.error)
+    }
+}
+
+impl std::error::Error for StepError {}
+
+fn validate(op: &Operation, width: usize, height: usize) -> Result<(), ScreenError> {
+    use Operation::*;
+    // A corner past usize::MAX is reported as usize::MAX.
+    let fits = |x: usize, y: usize, w: usize, h: usize| match (x.checked_add(w), y.checked_add(h)) {
+        (Some(right), Some(bottom)) if right <= width && bottom <= height => Ok(()),
+        _ => Err(ScreenError::RectOutOfBounds {
+            right: x.saturating_add(w),
+            bottom: y.saturating_add(h),
+            width,
+            height,
+        }),
+    };
+    match *op {
+        Clear => Ok(()),
+        Rect {
+            width: w,
+            height: h,
+        } => fits(0, 0, w, h),
+        RotateRow { y, .. } if y >= height => Err(ScreenError::RowOutOfBounds { y, height }),
+        RotateColumn { x, .. } if x >= width => Err(ScreenError::ColumnOutOfBounds { x, width }),
+        RotateRow { .. } | RotateColumn { .. } => Ok(()),
+        InvertRect {
+            x,
+            y,
+            width: w,
+            height: h,
+        } => fits(x, y, w, h),
+        RotateRect {
+            x,
+            y,
+            width: w,
+            height: h,
+            by,
+        } => {
+            fits(x, y, w, h)?;
+            if by % 2 == 1 && w != h {
+                return Err(ScreenError::NotSquare {
+                    width: w,
+                    height: h,
+                });
+            }
+            Ok(())
         }
     }
 }
 
+// The region turned a quarter turn clockwise `by` times. Odd turns are only
+// valid on squares, see `validate`.
+fn quarter_turns(region: &[Vec<bool>], by: usize) -> Vec<Vec<bool>> {
+    let height = region.len();
+    let width = region.first().map_or(0, Vec::len);
+    (0..height)
+        .map(|r| {
+            (0..width)
+                .map(|c| match by % 4 {
+                    0 => region[r][c],
+                    1 => region[height - 1 - c][r],
+                    2 => region[height - 1 - r][width - 1 - c],
+                    _ => region[c][width - 1 - r],
+                })
+                .collect()
+        })
+        .collect()
+}
+
+#[derive(Debug, Clone, PartialEq)]
 struct Screen(Vec<Vec<bool>>);
 
 impl Screen {
     fn new(width: usize, height: usize) -> Self {
+        assert!(width > 0 && height > 0, "Screen must not be empty");
         Screen(vec![vec![false; width]; height])
     }
 
+    fn width(&self) -> usize {
+        self.0[0].len()
+    }
+
+    fn height(&self) -> usize {
+        self.0.len()
+    }
+
+    fn clear(&mut self) {
+        for row in &mut self.0 {
+            row.fill(false);
+        }
+    }
+
     fn rect(&mut self, width: usize, height: usize) {
         for y in 0..height {
             for x in 0..width {
@@ -77,34 +541,86 @@ impl Screen {
     }
 
     fn rotate_row(&mut self, y: usize, by: usize) {
-        self.0[y].rotate_right(by);
+        let width = self.width();
+        self.0[y].rotate_right(by % width);
     }
 
     fn rotate_column(&mut self, x: usize, by: usize) {
         let mut column = self.0.iter().map(|row| row[x]).collect::<Vec<_>>();
-        column.rotate_right(by);
+        column.rotate_right(by % self.height());
         for (y, pixel) in self.0.iter_mut().enumerate() {
             pixel[x] = column[y];
         }
     }
 
-    fn run(&mut self, operations: &[Operation]) {
-        use Operation::*;
-        for op in operations {
-            match op {
-                Rect { width, height } => self.rect(*width, *height),
-                RotateRow { y, by } => self.rotate_row(*y, *by),
-                RotateColumn { x, by } => self.rotate_column(*x, *by),
+    fn invert_rect(&mut self, x: usize, y: usize, width: usize, height: usize) {
+        for row in &mut self.0[y..y + height] {
+            for pixel in &mut row[x..x + width] {
+                *pixel = !*pixel;
             }
         }
     }
 
+    fn rotate_rect(&mut self, x: usize, y: usize, width: usize, height: usize, by: usize) {
+        let region: Vec<Vec<bool>> = self.0[y..y + height]
+            .iter()
+            .map(|row| row[x..x + width].to_vec())
+            .collect();
+        for (r, row) in quarter_turns(&region, by).into_iter().enumerate() {
+            self.0[y + r][x..x + width].copy_from_slice(&row);
+        }
+    }
+
+    fn apply(&mut self, op: &Operation) -> Result<(), ScreenError> {
+        use Operation::*;
+        validate(op, self.width(), self.height())?;
+        match *op {
+            Clear => self.clear(),
+            Rect { width, height } => self.rect(width, height),
+            RotateRow { y, by } => self.rotate_row(y, by),
+            RotateColumn { x, by } => self.rotate_column(x, by),
+            InvertRect {
+                x,
+                y,
+                width,
+                height,
+            } => self.invert_rect(x, y, width, height),
+            RotateRect {
+                x,
+                y,
+                width,
+                height,
+                by,
+            } => self.rotate_rect(x, y, width, height, by),
+        }
+        Ok(())
+    }
+
+    // Stops at the first invalid operation, leaving the previous ones applied.
+    fn run(&mut self, operations: &[Operation]) -> Result<(), StepError> {
+        for (step, op) in operations.iter().enumerate() {
+            self.apply(op).map_err(|error| StepError { step, error })?;
+        }
+        Ok(())
+    }
+
     fn count_lit(&self) -> usize {
         self.0
             .iter()
             .map(|row| row.iter().filter(|&p| *p).count())
             .sum()
     }
+
+    // Plain (ASCII) PBM, where 1 is a black pixel.
+    fn to_pbm(&self) -> String {
+        let mut pbm = format!("P1\n{} {}\n", self.width(), self.height());
+        for row in &self.0 {
+            let pixels: Vec<&str> = row.iter().map(|&p| if p { "1" } else { "0" }).collect();
+            pbm.push_str(&pixels.join(" "));
+            pbm.push('\n');
+        }
+        pbm
+    }
 }
 
 impl fmt::Display for Screen {
@@ -165,8 +681,126 @@ mod tests {
         assert_eq!(screen.count_lit(), 6);
     }
 
+    fn example() -> Vec<Operation> {
+        ["rect 3x2", "rotate column x=1 by 1", "rotate row y=0 by 4"]
+            .iter()
+            .map(|s| s.parse().unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn test_frames() {
+        let input = example();
+        let frames = frames(&input, (50, 6)).unwrap();
+        assert_eq!(frames.len(), 4);
+        assert_eq!(frames[0].count_lit(), 0);
+        assert_eq!(frames[1].count_lit(), 6);
+        assert_eq!(caption(&input, 2), "Step 2/3: rotate column x=1 by 1");
+
+        let mut screen = Screen::new(3, 2);
+        screen.rect(2, 1);
+        assert_eq!(screen.to_pbm(), "P1\n3 2\n1 1 0\n0 0 0\n");
+    }
+
+    #[test]
+    fn test_asciicast() {
+        let input = example();
+        let cast = asciicast(&input, (50, 6), Duration::from_millis(250)).unwrap();
+        let lines: Vec<serde_json::Value> = cast
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(lines.len(), 5);
+        assert_eq!(lines[0]["version"], 2);
+        assert_eq!(lines[0]["height"], 7);
+        assert_eq!(lines[4][0], 0.75);
+        assert_eq!(lines[4][1], "o");
+        let data = lines[4][2].as_str().unwrap();
+        assert!(data.starts_with("\x1b[2J\x1b[HStep 3/3: rotate row y=0 by 4\r\n"));
+        assert!(!data.replace("\r\n", "").contains('\n'));
+        assert_eq!(data.split("\r\n").count(), 7);
+        assert!(data.ends_with('.') || data.ends_with('#'));
+    }
+
+    #[test]
+    fn test_new_operations() {
+        let input: Vec<Operation> = [
+            "rect 2x1",
+            "invert rect 3x3 at 1,0",
+            "rotate rect 3x3 at 1,0 by 1",
+            "rotate rect 4x1 by 2",
+        ]
+        .iter()
+        .map(|s| s.parse().unwrap())
+        .collect();
+        let frames = frames(&input, (4, 3)).unwrap();
+        assert_eq!(frames[2].to_string(), "#.##\n.###\n.###\n");
+        assert_eq!(frames[3].to_string(), "###.\n.###\n.###\n");
+        assert_eq!(frames[4].to_string(), ".###\n.###\n.###\n");
+        assert_eq!(input[2].to_string(), "rotate rect 3x3 at 1,0 by 1");
+
+        let cleared = final_screen(&[input[0].clone(), Operation::Clear], (4, 3)).unwrap();
+        assert_eq!(cleared.count_lit(), 0);
+        assert_eq!(required_size(&input), Some((4, 3)));
+    }
+
+    #[test]
+    fn test_validation() {
+        let error = |op: &str, size: (usize, usize)| {
+            final_screen(&[op.parse().unwrap()], size)
+                .unwrap_err()
+                .error
+        };
+        assert_eq!(
+            error("rotate row y=7 by 1", (50, 6)),
+            ScreenError::RowOutOfBounds { y: 7, height: 6 }
+        );
+        assert_eq!(
+            error("rotate column x=3 by 1", (3, 3)),
+            ScreenError::ColumnOutOfBounds { x: 3, width: 3 }
+        );
+        assert_eq!(
+            error("invert rect 2x2 at 2,1", (3, 3)),
+            ScreenError::RectOutOfBounds {
+                right: 4,
+                bottom: 3,
+                width: 3,
+                height: 3
+            }
+        );
+        assert_eq!(
+            error("rotate rect 2x1 by 3", (3, 3)),
+            ScreenError::NotSquare {
+                width: 2,
+                height: 1
+            }
+        );
+        assert_eq!(
+            error("invert rect 1x1 at 18446744073709551615,0", (3, 3)),
+            ScreenError::RectOutOfBounds {
+                right: usize::MAX,
+                bottom: 1,
+                width: 3,
+                height: 3
+            }
+        );
+        let far = ["rotate row y=18446744073709551615 by 1".parse().unwrap()];
+        assert_eq!(required_size(&far), None);
+        let far = ["rotate rect 2x2 at 1,18446744073709551615 by 1"
+            .parse()
+            .unwrap()];
+        assert_eq!(required_size(&far), None);
+        assert!("invert rect 1x1 by 2".parse::<Operation>().is_err());
+        assert!("rect 1x99999999999999999999".parse::<Operation>().is_err());
+    }
+
     #[test]
     fn test_part1() {
         assert_eq!(part1(&INPUT), 121);
     }
+
+    #[test]
+    fn test_part2() {
+        assert_eq!(part2(&INPUT), Ok("RURUCEOEIL".to_string()));
+    }
 }
@@ -0,0 +1,350 @@
+use crate::{Operation, ScreenError, StepError, quarter_turns, validate};
+use std::fmt;
+
+// Same screen as `Screen`, with every row packed into u64 words: pixel x of a
+// row is bit x % 64 of word x / 64. Bits past the width are always clear.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PackedScreen {
+    width: usize,
+    height: usize,
+    words: usize,
+    bits: Vec<u64>,
+}
+
+impl PackedScreen {
+    pub fn new(width: usize, height: usize) -> Self {
+        assert!(width > 0 && height > 0, "Screen must not be empty");
+        let words = width.div_ceil(64);
+        Self {
+            width,
+            height,
+            words,
+            bits: vec![0; words * height],
+        }
+    }
+
+    fn row_mut(&mut self, y: usize) -> &mut [u64] {
+        &mut self.bits[y * self.words..(y + 1) * self.words]
+    }
+
+    pub fn get(&self, x: usize, y: usize) -> bool {
+        self.bits[y * self.words + x / 64] >> (x % 64) & 1 == 1
+    }
+
+    fn set(&mut self, x: usize, y: usize, on: bool) {
+        let word = &mut self.bits[y * self.words + x / 64];
+        if on {
+            *word |= 1 << (x % 64);
+        } else {
+            *word &= !(1 << (x % 64));
+        }
+    }
+
+    fn rect(&mut self, width: usize, height: usize) {
+        for y in 0..height {
+            update_range(self.row_mut(y), 0, width, |word, mask| word | mask);
+        }
+    }
+
+    fn invert_rect(&mut self, x: usize, y: usize, width: usize, height: usize) {
+        for y in y..y + height {
+            update_range(self.row_mut(y), x, x + width, |word, mask| word ^ mask);
+        }
+    }
+
+    fn rotate_row(&mut self, y: usize, by: usize) {
+        let width = self.width;
+        rotate_bits(self.row_mut(y), width, by % width);
+    }
+
+    // Transposes the column into a packed vector of `height` bits, rotates it
+    // like a row and transposes it back. The word column holding `x` is
+    // transposed 64 rows at a time, so that column `x` becomes one word.
+    fn rotate_column(&mut self, x: usize, by: usize) {
+        let (word, bit) = (x / 64, x % 64);
+        let mut blocks: Vec<[u64; 64]> = (0..self.height.div_ceil(64))
+            .map(|b| {
+                let mut block = self.load_block(b, word);
+                transpose(&mut block);
+                block
+            })
+            .collect();
+        let mut column: Vec<u64> = blocks.iter().map(|block| block[bit]).collect();
+        rotate_bits(&mut column, self.height, by % self.height);
+        for (b, block) in blocks.iter_mut().enumerate() {
+            block[bit] = column[b];
+            transpose(block);
+            self.store_block(b, word, block);
+        }
+    }
+
+    // Word `word` of the rows 64 * b .. 64 * b + 63, with zeros past the bottom.
+    fn load_block(&self, b: usize, word: usize) -> [u64; 64] {
+        let mut block = [0u64; 64];
+        for (r, row) in block.iter_mut().enumerate() {
+            let y = b * 64 + r;
+            if y < self.height {
+                *row = self.bits[y * self.words + word];
+            }
+        }
+        block
+    }
+
+    fn store_block(&mut self, b: usize, word: usize, block: &[u64; 64]) {
+        for (r, &row) in block.iter().enumerate() {
+            let y = b * 64 + r;
+            if y < self.height {
+                self.bits[y * self.words + word] = row;
+            }
+        }
+    }
+
+    fn rotate_rect(&mut self, x: usize, y: usize, width: usize, height: usize, by: usize) {
+        let region: Vec<Vec<bool>> = (y..y + height)
+            .map(|y| (x..x + width).map(|x| self.get(x, y)).collect())
+            .collect();
+        for (r, row) in quarter_turns(&region, by).iter().enumerate() {
+            for (c, &pixel) in row.iter().enumerate() {
+                self.set(x + c, y + r, pixel);
+            }
+        }
+    }
+
+    pub fn apply(&mut self, op: &Operation) -> Result<(), ScreenError> {
+        use Operation::*;
+        validate(op, self.width, self.height)?;
+        match *op {
+            Clear => self.bits.fill(0),
+            Rect { width, height } => self.rect(width, height),
+            RotateRow { y, by } => self.rotate_row(y, by),
+            RotateColumn { x, by } => self.rotate_column(x, by),
+            InvertRect {
+                x,
+                y,
+                width,
+                height,
+            } => self.invert_rect(x, y, width, height),
+            RotateRect {
+                x,
+                y,
+                width,
+                height,
+                by,
+            } => self.rotate_rect(x, y, width, height, by),
+        }
+        Ok(())
+    }
+
+    pub fn run(&mut self, operations: &[Operation]) -> Result<(), StepError> {
+        for (step, op) in operations.iter().enumerate() {
+            self.apply(op).map_err(|error| StepError { step, error })?;
+        }
+        Ok(())
+    }
+
+    pub fn count_lit(&self) -> usize {
+        self.bits
+            .iter()
+            .map(|word| word.count_ones() as usize)
+            .sum()
+    }
+}
+
+impl fmt::Display for PackedScreen {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for y in 0..self.height {
+            for x in 0..self.width {
+                write!(f, "{}", if self.get(x, y) { '#' } else { '.' })?;
+            }
+            writeln!(f)?;
+        }
+        Ok(())
+    }
+}
+
+// Applies `update(word, mask)` to the bits `from..to` of a packed row.
+fn update_range(row: &mut [u64], from: usize, to: usize, update: impl Fn(u64, u64) -> u64) {
+    if from >= to {
+        return;
+    }
+    let words = row.iter_mut().enumerate();
+    for (w, word) in words.take((to - 1) / 64 + 1).skip(from / 64) {
+        let lo = from.max(w * 64) - w * 64;
+        let hi = to.min(w * 64 + 64) - w * 64;
+        let mask = if hi - lo == 64 {
+            !0
+        } else {
+            ((1 << (hi - lo)) - 1) << lo
+        };
+        *word = update(*word, mask);
+    }
+}
+
+// Transposes a 64x64 bit matrix in place (bit c of row r ends up as bit r of
+// row c) by swapping ever smaller off-diagonal blocks.
+fn transpose(block: &mut [u64; 64]) {
+    let mut j = 32;
+    let mut mask: u64 = 0x0000_0000_FFFF_FFFF;
+    while j != 0 {
+        let mut k = 0;
+        while k < 64 {
+            let t = ((block[k] >> j) ^ block[k + j]) & mask;
+            block[k + j] ^= t;
+            block[k] ^= t << j;
+            k = (k + j + 1) & !j;
+        }
+        j >>= 1;
+        mask ^= mask << j;
+    }
+}
+
+// Rotates the first `len` bits of `words` towards the higher indices.
+fn rotate_bits(words: &mut [u64], len: usize, by: usize) {
+    if by == 0 {
+        return;
+    }
+    let up = shifted_up(words, by);
+    let down = shifted_down(words, len - by);
+    for (w, word) in words.iter_mut().enumerate() {
+        *word = up[w] | down[w];
+    }
+    if !len.is_multiple_of(64) {
+        *words.last_mut().unwrap() &= (1 << (len % 64)) - 1;
+    }
+}
+
+fn shifted_up(words: &[u64], n: usize) -> Vec<u64> {
+    let (q, r) = (n / 64, n % 64);
+    (0..words.len())
+        .map(|w| {
+            let Some(src) = w.checked_sub(q) else {
+                return 0;
+            };
+            let carry = match src.checked_sub(1) {
+                Some(below) if r > 0 => words[below] >> (64 - r),
+                _ => 0,
+            };
+            words[src] << r | carry
+        })
+        .collect()
+}
+
+fn shifted_down(words: &[u64], n: usize) -> Vec<u64> {
+    let (q, r) = (n / 64, n % 64);
+    (0..words.len())
+        .map(|w| {
+            let Some(&word) = words.get(w + q) else {
+                return 0;
+            };
+            let carry = match words.get(w + q + 1) {
+                Some(&above) if r > 0 => above << (64 - r),
+                _ => 0,
+            };
+            word >> r | carry
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Screen, synthetic};
+    use proptest::prelude::*;
+
+    fn operation() -> impl Strategy<Value = Operation> {
+        let n = || 0..140_usize;
+        prop_oneof![
+            1 => Just(Operation::Clear),
+            3 => (n(), n()).prop_map(|(width, height)| Operation::Rect { width, height }),
+            4 => (n(), n()).prop_map(|(y, by)| Operation::RotateRow { y, by }),
+            4 => (n(), n()).prop_map(|(x, by)| Operation::RotateColumn { x, by }),
+            2 => (n(), n(), n(), n()).prop_map(|(x, y, width, height)| Operation::InvertRect {
+                x,
+                y,
+                width,
+                height
+            }),
+            2 => (n(), n(), 0..20_usize, 0..20_usize, n()).prop_map(
+                |(x, y, width, height, by)| Operation::RotateRect {
+                    x,
+                    y,
+                    width,
+                    height,
+                    by
+                }
+            ),
+        ]
+    }
+
+    #[test]
+    fn test_rotate_bits() {
+        let mut words = [0b1011, 1 << 63, 0b1];
+        rotate_bits(&mut words, 130, 2);
+        assert_eq!(words, [0b101101, 0, 0b10]);
+        rotate_bits(&mut words, 130, 128);
+        assert_eq!(words, [0b1011, 1 << 63, 0b1]);
+    }
+
+    #[test]
+    fn test_transpose() {
+        let mut block = [0u64; 64];
+        let mut state = 0x9E37_79B9_7F4A_7C15_u64;
+        for row in block.iter_mut() {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            *row = state;
+        }
+        let original = block;
+        transpose(&mut block);
+        for (r, row) in original.iter().enumerate() {
+            for (c, transposed) in block.iter().enumerate() {
+                assert_eq!(transposed >> r & 1, row >> c & 1);
+            }
+        }
+        transpose(&mut block);
+        assert_eq!(block, original);
+    }
+
+    #[test]
+    fn test_out_of_bounds() {
+        let mut packed = PackedScreen::new(3, 3);
+        let op = "invert rect 1x1 at 18446744073709551615,0".parse().unwrap();
+        assert_eq!(
+            packed.apply(&op),
+            Err(ScreenError::RectOutOfBounds {
+                right: usize::MAX,
+                bottom: 1,
+                width: 3,
+                height: 3
+            })
+        );
+        assert_eq!(packed.count_lit(), 0);
+    }
+
+    #[test]
+    fn test_synthetic_matches_screen() {
+        let input = synthetic(3000, (300, 70), 8);
+        let mut screen = Screen::new(300, 70);
+        let mut packed = PackedScreen::new(300, 70);
+        screen.run(&input).unwrap();
+        packed.run(&input).unwrap();
+        assert_eq!(packed.to_string(), screen.to_string());
+        assert_eq!(packed.count_lit(), screen.count_lit());
+    }
+
+    proptest! {
+        #[test]
+        fn test_matches_screen(
+            width in 1..140_usize,
+            height in 1..100_usize,
+            input in proptest::collection::vec(operation(), 0..40),
+        ) {
+            let mut screen = Screen::new(width, height);
+            let mut packed = PackedScreen::new(width, height);
+            prop_assert_eq!(packed.run(&input), screen.run(&input));
+            prop_assert_eq!(packed.to_string(), screen.to_string());
+            prop_assert_eq!(packed.count_lit(), screen.count_lit());
+        }
+    }
+}
@@ -1,74 +1,347 @@
 use once_cell::sync::Lazy;
+use std::io::{self, Read, Write};
 
 static INPUT: Lazy<String> = Lazy::new(|| common::read_file_as_string("data/day09.txt").unwrap());
 
 fn main() {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let value = |name: &str| {
+        args.iter()
+            .position(|arg| arg == name)
+            .map(|i| args.get(i + 1).expect("Missing option value"))
+    };
+    if let Some(version) = value("--decompress") {
+        let version = match version.as_str() {
+            "1" => Version::V1,
+            "2" => Version::V2,
+            _ => panic!("Version must be 1 or 2"),
+        };
+        let limit = value("--limit").map_or(u64::MAX, |n| n.parse().unwrap());
+        let mut output = io::BufWriter::new(io::stdout().lock());
+        let input = strip_whitespace(&INPUT);
+        io::copy(
+            &mut Decompressor::new(&input, version).take(limit),
+            &mut output,
+        )
+        .unwrap();
+        output.flush().unwrap();
+        return;
+    }
+    if args.iter().any(|arg| arg == "--compress") {
+        let mut data = Vec::new();
+        io::stdin().read_to_end(&mut data).unwrap();
+        io::stdout().write_all(&compress(&data)).unwrap();
+        return;
+    }
     println!("Part 1: {}", part1(&INPUT));
     println!("Part 2: {}", part2(&INPUT));
 }
 
 fn part1(input: &str) -> usize {
-    part(input.as_bytes(), |r| r.len() )
+    decompressed_length(&strip_whitespace(input), Version::V1).unwrap()
 }
 
 fn part2(input: &str) -> usize {
-    part2_bytes(input.as_bytes())
+    decompressed_length(&strip_whitespace(input), Version::V2).unwrap()
+}
+
+// Whitespace is ignored by the format, so it is removed before parsing and
+// error offsets refer to the stripped input.
+fn strip_whitespace(input: &str) -> Vec<u8> {
+    input.bytes().filter(|b| !b.is_ascii_whitespace()).collect()
 }
 
-fn part2_bytes(input: &[u8]) -> usize {
-    part(input, |r| part2_bytes(r) )
+fn decompressed_length(input: &[u8], version: Version) -> Result<usize, MarkerError> {
+    match version {
+        Version::V1 => part(input, 0, |span, _| Ok(span.len())),
+        Version::V2 => v2_length(input, 0),
+    }
 }
 
-fn part<F>(input: &[u8], count: F) -> usize
+fn v2_length(input: &[u8], offset: usize) -> Result<usize, MarkerError> {
+    part(input, offset, v2_length)
+}
+
+// `offset` is the position of `input` in the whole text, for error reporting.
+fn part<F>(input: &[u8], offset: usize, count: F) -> Result<usize, MarkerError>
 where
-    F: Fn(&[u8]) -> usize,
+    F: Fn(&[u8], usize) -> Result<usize, MarkerError>,
 {
-    enum ParserState {
-        Regular(usize),
-        FirstNum(usize),
-        SecondNum(usize, usize),
-    }
-
-    let mut rest = input;
-    let mut result = 0;
-    let mut state = ParserState::Regular(0);
-    while !rest.is_empty() {
-        let current = rest[0];
-        rest = &rest[1..];
-        match state {
-            ParserState::Regular(x) => match current {
-                b'(' => {
-                    result += x;
-                    state = ParserState::FirstNum(0);
-                }
-                _ => state = ParserState::Regular(x + 1),
-            },
-            ParserState::FirstNum(x) => match current {
-                b'x' => {
-                    state = ParserState::SecondNum(x, 0);
-                }
-                c => {
-                    let digit = c - b'0';
-                    state = ParserState::FirstNum(10 * x + digit as usize);
+    let mut pos = 0;
+    let mut result: usize = 0;
+    while pos < input.len() {
+        let rest = &input[pos..];
+        let expanded = if rest[0] == b'(' {
+            let marker = parse_marker(rest, offset + pos)?;
+            let span = &rest[marker.size..marker.size + marker.length];
+            pos += marker.size + marker.length;
+            count(span, offset + pos - marker.length)?
+                .checked_mul(marker.copies)
+                .ok_or(MarkerError::Overflow {
+                    offset: offset + pos,
+                })?
+        } else {
+            let run = rest.iter().position(|&b| b == b'(').unwrap_or(rest.len());
+            pos += run;
+            run
+        };
+        result = result.checked_add(expanded).ok_or(MarkerError::Overflow {
+            offset: offset + pos,
+        })?;
+    }
+    Ok(result)
+}
+
+#[derive(Debug, PartialEq)]
+enum MarkerError {
+    NonDigit {
+        offset: usize,
+        byte: u8,
+    },
+    EmptyNumber {
+        offset: usize,
+    },
+    Unterminated {
+        offset: usize,
+    },
+    SpanTooLong {
+        offset: usize,
+        length: usize,
+        available: usize,
+    },
+    Overflow {
+        offset: usize,
+    },
+}
+
+impl std::fmt::Display for MarkerError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            MarkerError::NonDigit { offset, byte } => write!(
+                f,
+                "Unexpected {:?} in marker at offset {}",
+                *byte as char, offset
+            ),
+            MarkerError::EmptyNumber { offset } => {
+                write!(f, "Missing number in marker at offset {}", offset)
+            }
+            MarkerError::Unterminated { offset } => {
+                write!(f, "Marker at offset {} is never closed", offset)
+            }
+            MarkerError::SpanTooLong {
+                offset,
+                length,
+                available,
+            } => write!(
+                f,
+                "Marker at offset {} repeats {} bytes but only {} follow",
+                offset, length, available
+            ),
+            MarkerError::Overflow { offset } => {
+                write!(f, "Length overflows at offset {}", offset)
+            }
+        }
+    }
+}
+
+impl std::error::Error for MarkerError {}
+
+struct Marker {
+    length: usize,
+    copies: usize,
+    // Bytes taken by the marker itself.
+    size: usize,
+}
+
+// Parses the `(AxB)` marker at the start of `data`, checking that the span it
+// repeats fits in `data`. `offset` is the position of `data` in the input.
+fn parse_marker(data: &[u8], offset: usize) -> Result<Marker, MarkerError> {
+    let mut numbers = [0usize; 2];
+    let mut digits = 0;
+    let mut index = 0;
+    for (i, &byte) in data.iter().enumerate().skip(1) {
+        match (byte, index) {
+            (b'0'..=b'9', _) => {
+                numbers[index] = numbers[index]
+                    .checked_mul(10)
+                    .and_then(|n| n.checked_add((byte - b'0') as usize))
+                    .ok_or(MarkerError::Overflow { offset: offset + i })?;
+                digits += 1;
+            }
+            (b'x', 0) | (b')', 1) if digits == 0 => {
+                return Err(MarkerError::EmptyNumber { offset: offset + i });
+            }
+            (b'x', 0) => {
+                index = 1;
+                digits = 0;
+            }
+            (b')', 1) => {
+                let [length, copies] = numbers;
+                let size = i + 1;
+                let available = data.len() - size;
+                if length > available {
+                    return Err(MarkerError::SpanTooLong {
+                        offset,
+                        length,
+                        available,
+                    });
                 }
-            },
-            ParserState::SecondNum(x, y) => match current {
-                b')' => {
-                    result += y * count(&rest[..x]);
-                    state = ParserState::Regular(0);
-                    rest = &rest[x..];
+                return Ok(Marker {
+                    length,
+                    copies,
+                    size,
+                });
+            }
+            _ => {
+                return Err(MarkerError::NonDigit {
+                    offset: offset + i,
+                    byte,
+                });
+            }
+        }
+    }
+    Err(MarkerError::Unterminated { offset })
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Version {
+    // Markers inside a repeated span are plain data.
+    V1,
+    // Markers inside a repeated span are expanded too.
+    V2,
+}
+
+// A span being repeated: `data[pos..]` is what is left of the current copy,
+// and `start` the offset of `data` in the input.
+struct Frame<'a> {
+    data: &'a [u8],
+    start: usize,
+    pos: usize,
+    copies_left: usize,
+    literal: bool,
+}
+
+// Expands markers lazily while being read, keeping only one frame per level
+// of nested markers, so the output never has to fit in memory.
+struct Decompressor<'a> {
+    stack: Vec<Frame<'a>>,
+    version: Version,
+    // A bad marker met after some bytes were already read, reported on the
+    // next call.
+    pending: Option<MarkerError>,
+}
+
+impl<'a> Decompressor<'a> {
+    fn new(input: &'a [u8], version: Version) -> Self {
+        Self {
+            stack: vec![Frame {
+                data: input,
+                start: 0,
+                pos: 0,
+                copies_left: 1,
+                literal: false,
+            }],
+            version,
+            pending: None,
+        }
+    }
+}
+
+impl Read for Decompressor<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if let Some(e) = self.pending.take() {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, e));
+        }
+        let mut written = 0;
+        while written < buf.len() {
+            let Some(frame) = self.stack.last_mut() else {
+                break;
+            };
+            if frame.pos == frame.data.len() {
+                if frame.copies_left > 1 {
+                    frame.copies_left -= 1;
+                    frame.pos = 0;
+                } else {
+                    self.stack.pop();
                 }
-                c => {
-                    let digit = c - b'0';
-                    state = ParserState::SecondNum(x, 10 * y + digit as usize);
+                continue;
+            }
+            let rest = &frame.data[frame.pos..];
+            if !frame.literal && rest[0] == b'(' {
+                let marker = match parse_marker(rest, frame.start + frame.pos) {
+                    Ok(marker) => marker,
+                    Err(e) if written > 0 => {
+                        self.pending = Some(e);
+                        break;
+                    }
+                    Err(e) => return Err(io::Error::new(io::ErrorKind::InvalidData, e)),
+                };
+                let span = &rest[marker.size..marker.size + marker.length];
+                frame.pos += marker.size;
+                let start = frame.start + frame.pos;
+                frame.pos += marker.length;
+                if marker.length > 0 && marker.copies > 0 {
+                    self.stack.push(Frame {
+                        data: span,
+                        start,
+                        pos: 0,
+                        copies_left: marker.copies,
+                        literal: self.version == Version::V1,
+                    });
                 }
-            },
+                continue;
+            }
+            let run = if frame.literal {
+                rest.len()
+            } else {
+                rest.iter().position(|&b| b == b'(').unwrap_or(rest.len())
+            };
+            let n = run.min(buf.len() - written);
+            buf[written..written + n].copy_from_slice(&rest[..n]);
+            frame.pos += n;
+            written += n;
         }
+        Ok(written)
     }
-    if let ParserState::Regular(x) = state {
-        return result + x;
+}
+
+// Greedily replaces runs of a repeated chunk (at most MAX_PERIOD bytes) by a
+// marker when it saves space. The output decompresses back to `data` as
+// version 1, and as version 2 as well when `data` has no '('.
+fn compress(data: &[u8]) -> Vec<u8> {
+    const MAX_PERIOD: usize = 32;
+    let mut output = Vec::new();
+    let mut i = 0;
+    while i < data.len() {
+        let mut best: Option<(usize, usize, usize)> = None;
+        for period in 1..=MAX_PERIOD.min((data.len() - i) / 2) {
+            let chunk = &data[i..i + period];
+            let copies = 1 + data[i + period..]
+                .chunks_exact(period)
+                .take_while(|c| *c == chunk)
+                .count();
+            let encoded = format!("({}x{})", period, copies).len() + period;
+            let saved = (period * copies).saturating_sub(encoded);
+            if copies > 1 && saved > best.map_or(0, |(_, _, saved)| saved) {
+                best = Some((period, copies, saved));
+            }
+        }
+        match best {
+            Some((period, copies, _)) => {
+                output.extend_from_slice(format!("({}x{})", period, copies).as_bytes());
+                output.extend_from_slice(&data[i..i + period]);
+                i += period * copies;
+            }
+            None => {
+                if data[i] == b'(' {
+                    output.extend_from_slice(b"(1x1)");
+                }
+                output.push(data[i]);
+                i += 1;
+            }
+        }
     }
-    unreachable!("should have gotten a state");
+    output
 }
 
 #[cfg(test)]
@@ -103,6 +376,136 @@ mod tests {
         );
     }
 
+    fn decompress(input: &str, version: Version) -> String {
+        let mut output = String::new();
+        Decompressor::new(input.as_bytes(), version)
+            .read_to_string(&mut output)
+            .unwrap();
+        output
+    }
+
+    #[test]
+    fn test_decompress() {
+        assert_eq!(decompress("A(2x2)BCD(2x2)EFG", Version::V1), "ABCBCDEFEFG");
+        assert_eq!(
+            decompress("X(8x2)(3x3)ABCY", Version::V1),
+            "X(3x3)ABC(3x3)ABCY"
+        );
+        assert_eq!(
+            decompress("X(8x2)(3x3)ABCY", Version::V2),
+            "XABCABCABCABCABCABCY"
+        );
+        assert!(
+            Decompressor::new(b"A(2x)BC", Version::V1)
+                .read_to_end(&mut Vec::new())
+                .is_err()
+        );
+        assert!(
+            Decompressor::new(b"A(5x2)BC", Version::V1)
+                .read_to_end(&mut Vec::new())
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn test_streaming() {
+        let input = "(27x12)(20x12)(13x14)(7x10)(1x12)A";
+        let mut decompressor = Decompressor::new(input.as_bytes(), Version::V2);
+        assert_eq!(
+            io::copy(&mut decompressor, &mut io::sink()).unwrap(),
+            241920
+        );
+
+        let input = "(25x3)(3x3)ABC(2x3)XY(5x2)PQRSTX(18x9)(3x2)TWO(5x7)SEVEN";
+        let mut head = String::new();
+        Decompressor::new(input.as_bytes(), Version::V2)
+            .take(12)
+            .read_to_string(&mut head)
+            .unwrap();
+        assert_eq!(head, "ABCABCABCXYX");
+    }
+
+    #[test]
+    fn test_compress() {
+        for data in [
+            "ADVENT",
+            "ABABABABABABABABXYZXYZXYZXYZ",
+            "AAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAB",
+            "(3x3)XYZ(3x3)XYZ(3x3)XYZ(3x3)XYZ",
+        ] {
+            let compressed = String::from_utf8(compress(data.as_bytes())).unwrap();
+            assert_eq!(decompress(&compressed, Version::V1), data);
+            if !data.contains('(') {
+                assert_eq!(decompress(&compressed, Version::V2), data);
+            }
+        }
+        assert_eq!(compress(b"ABABABABABAB"), b"(2x6)AB");
+    }
+
+    #[test]
+    fn test_marker_errors() {
+        let error = |input: &str| decompressed_length(input.as_bytes(), Version::V2).unwrap_err();
+        assert_eq!(
+            error("AB(1a2)C"),
+            MarkerError::NonDigit {
+                offset: 4,
+                byte: b'a'
+            }
+        );
+        assert_eq!(error("AB(x2)C"), MarkerError::EmptyNumber { offset: 3 });
+        assert_eq!(error("AB(12x3"), MarkerError::Unterminated { offset: 2 });
+        assert_eq!(
+            error("A(2x2)BC(5x1)(1x3)AB"),
+            MarkerError::SpanTooLong {
+                offset: 13,
+                length: 1,
+                available: 0,
+            }
+        );
+        assert_eq!(
+            error("AB(9x1)C"),
+            MarkerError::SpanTooLong {
+                offset: 2,
+                length: 9,
+                available: 1,
+            }
+        );
+        assert_eq!(
+            error("(4x2)AB(9x1)C"),
+            MarkerError::Unterminated { offset: 7 }
+        );
+        assert_eq!(
+            error("(99999999999999999999x1)A"),
+            MarkerError::Overflow { offset: 20 }
+        );
+        assert_eq!(
+            error("(15x4294967296)(1x4294967296)A"),
+            MarkerError::Overflow { offset: 30 }
+        );
+
+        let mut output = Vec::new();
+        let e = Decompressor::new(b"AB(3x2)CD", Version::V1)
+            .read_to_end(&mut output)
+            .unwrap_err();
+        assert_eq!(
+            e.to_string(),
+            "Marker at offset 2 repeats 3 bytes but only 2 follow"
+        );
+        assert_eq!(output, b"AB");
+
+        let mut decompressor = Decompressor::new(b"AB(3x2)CD", Version::V1);
+        let mut buf = [0; 8];
+        assert_eq!(decompressor.read(&mut buf).unwrap(), 2);
+        assert_eq!(&buf[..2], b"AB");
+        assert!(decompressor.read(&mut buf).is_err());
+    }
+
+    #[test]
+    fn test_whitespace() {
+        assert_eq!(part1("A(1x5)B C\n"), 7);
+        assert_eq!(part2("X(8x2)(3x3)A BCY"), 20);
+    }
+
     #[test]
     fn test_part2() {
         assert_eq!(part2(&INPUT), 10931789799);
@@ -1,7 +1,7 @@
 use once_cell::sync::Lazy;
 use regex::Regex;
 use std::cmp::{max, min};
-use std::collections::{HashMap, VecDeque};
+use std::collections::{BTreeMap, BTreeSet, HashMap, VecDeque};
 use std::str::FromStr;
 
 static PARSED_INPUT: Lazy<(Vec<Bot>, Vec<Transfer>)> = Lazy::new(|| {
@@ -10,7 +10,46 @@ static PARSED_INPUT: Lazy<(Vec<Bot>, Vec<Transfer>)> = Lazy::new(|| {
 });
 
 fn main() {
+    let args: Vec<String> = std::env::args().skip(1).collect();
     let mut factory = Factory::new(&PARSED_INPUT.0);
+    if args.iter().any(|arg| arg == "--events") {
+        factory.run(&PARSED_INPUT.1);
+        for event in &factory.events {
+            println!("{}", event);
+        }
+        return;
+    }
+    if args.iter().any(|arg| arg == "--dot") {
+        factory.run(&PARSED_INPUT.1);
+        print!("{}", factory.to_dot());
+        return;
+    }
+    if let Some(i) = args.iter().position(|arg| arg == "--compare") {
+        let chip = |j: usize| -> u32 { args.get(j).expect("Missing chip").parse().unwrap() };
+        let (a, b) = (chip(i + 1), chip(i + 2));
+        factory.run(&PARSED_INPUT.1);
+        match factory.comparer(a, b) {
+            Some(bot) => println!("Bot {} compares {} and {}", bot, a, b),
+            None => println!("No bot compares {} and {}", a, b),
+        }
+        return;
+    }
+    if args.iter().any(|arg| arg == "--topological") {
+        match solve(&PARSED_INPUT.0, &PARSED_INPUT.1) {
+            Ok(solution) => {
+                println!("Part 1: {:?}", solution.comparer(17, 61));
+                let product: u32 = (0..3).map(|i| solution.outputs[&i][0]).product();
+                println!("Part 2: {}", product);
+                println!("Idle bots: {:?}", solution.idle_bots);
+                println!("Unreachable outputs: {:?}", solution.unreachable_outputs);
+            }
+            Err(e) => {
+                eprintln!("{}", e);
+                std::process::exit(1);
+            }
+        }
+        return;
+    }
     let part1 = part1(&mut factory, &PARSED_INPUT.1, 17, 61);
     println!("Part 1: {:?}", part1.unwrap());
     println!("Part 2: {:?}", part2(&factory));
@@ -22,27 +61,53 @@ fn part1(
     min_target: u32,
     max_target: u32,
 ) -> Option<u32> {
-    let mut queue: VecDeque<_> = transfers.iter().cloned().collect();
-    let mut comparer_bot = None;
-    while !queue.is_empty() {
-        let transfer = queue.pop_front().unwrap();
-        let next = factory.step(&transfer);
-        if next.len() == 2 && next[0].value == min_target && next[1].value == max_target {
-            comparer_bot = Some(transfer.bot);
-        }
-        queue.extend(next.into_iter());
-    }
-    comparer_bot
+    factory.run(transfers);
+    factory.comparer(min_target, max_target)
 }
 
 fn part2(factory: &Factory) -> u32 {
     factory.output[&0] * factory.output[&1] * factory.output[&2]
 }
 
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Event {
+    ValueToBot { value: u32, bot: u32 },
+    BotCompared { bot: u32, low: u32, high: u32 },
+    ValueToOutput { bot: u32, value: u32, output: u32 },
+}
+
+impl std::fmt::Display for Event {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Event::ValueToBot { value, bot } => write!(f, "value {} goes to bot {}", value, bot),
+            Event::BotCompared { bot, low, high } => {
+                write!(f, "bot {} compares {} with {}", bot, low, high)
+            }
+            Event::ValueToOutput { bot, value, output } => {
+                write!(f, "bot {} puts {} in output {}", bot, value, output)
+            }
+        }
+    }
+}
+
+// The bot that compared chips a and b, in either order.
+fn comparer(events: &[Event], a: u32, b: u32) -> Option<u32> {
+    let (low, high) = (min(a, b), max(a, b));
+    events.iter().find_map(|event| match *event {
+        Event::BotCompared {
+            bot,
+            low: l,
+            high: h,
+        } if (l, h) == (low, high) => Some(bot),
+        _ => None,
+    })
+}
+
 struct Factory {
     bots: HashMap<u32, Bot>,
     holding: HashMap<u32, Option<u32>>,
     output: HashMap<u32, u32>,
+    events: Vec<Event>,
 }
 
 impl Factory {
@@ -51,9 +116,97 @@ impl Factory {
             bots: bots.iter().map(|b| (b.id, *b)).collect(),
             holding: bots.iter().map(|b| (b.id, None)).collect(),
             output: HashMap::new(),
+            events: Vec::new(),
         }
     }
 
+    // Feeds the input values, then every chip handed over, until no bot
+    // holds two chips.
+    fn run(&mut self, transfers: &[Transfer]) {
+        // Input values are flagged so that they are logged when delivered.
+        let mut queue: VecDeque<_> = transfers.iter().map(|t| (t.clone(), true)).collect();
+        while let Some((transfer, input)) = queue.pop_front() {
+            if input {
+                self.events.push(Event::ValueToBot {
+                    value: transfer.value,
+                    bot: transfer.bot,
+                });
+            }
+            queue.extend(self.step(&transfer).into_iter().map(|t| (t, false)));
+        }
+    }
+
+    // The bot that compared chips `a` and `b`, in either order.
+    fn comparer(&self, a: u32, b: u32) -> Option<u32> {
+        comparer(&self.events, a, b)
+    }
+
+    // Graphviz digraph of the wiring, each edge labelled with the chips that
+    // went through it (dashed when none did).
+    fn to_dot(&self) -> String {
+        let mut flows: BTreeMap<(u32, &str), Vec<u32>> = BTreeMap::new();
+        for event in &self.events {
+            if let Event::BotCompared { bot, low, high } = *event {
+                flows.entry((bot, "low")).or_default().push(low);
+                flows.entry((bot, "high")).or_default().push(high);
+            }
+        }
+        let node = |receiver: Receiver| match receiver {
+            Receiver::Bot(id) => format!("\"bot {}\"", id),
+            Receiver::Output(id) => format!("\"output {}\"", id),
+        };
+
+        let mut dot = String::from("digraph factory {\n");
+        let mut bots: Vec<&Bot> = self.bots.values().collect();
+        bots.sort_by_key(|bot| bot.id);
+        let mut outputs: Vec<u32> = bots
+            .iter()
+            .flat_map(|bot| [bot.low, bot.high])
+            .filter_map(|receiver| match receiver {
+                Receiver::Output(id) => Some(id),
+                Receiver::Bot(_) => None,
+            })
+            .collect();
+        outputs.sort_unstable();
+        outputs.dedup();
+        for id in outputs {
+            dot.push_str(&format!("  \"output {}\" [shape=box];\n", id));
+        }
+        for event in &self.events {
+            if let Event::ValueToBot { value, bot } = *event {
+                dot.push_str(&format!(
+                    "  \"value {}\" [shape=plaintext];\n  \"value {}\" -> \"bot {}\";\n",
+                    value, value, bot
+                ));
+            }
+        }
+        for bot in bots {
+            for (kind, receiver) in [("low", bot.low), ("high", bot.high)] {
+                let from = node(Receiver::Bot(bot.id));
+                match flows.get(&(bot.id, kind)) {
+                    Some(chips) => {
+                        let chips: Vec<String> = chips.iter().map(u32::to_string).collect();
+                        dot.push_str(&format!(
+                            "  {} -> {} [label=\"{}: {}\"];\n",
+                            from,
+                            node(receiver),
+                            kind,
+                            chips.join(", ")
+                        ));
+                    }
+                    None => dot.push_str(&format!(
+                        "  {} -> {} [label=\"{}\", style=dashed];\n",
+                        from,
+                        node(receiver),
+                        kind
+                    )),
+                }
+            }
+        }
+        dot.push_str("}\n");
+        dot
+    }
+
     fn step(&mut self, transfer: &Transfer) -> Vec<Transfer> {
         match self.holding.get_mut(&transfer.bot).and_then(Option::take) {
             None => {
@@ -61,8 +214,13 @@ impl Factory {
                 Vec::new()
             }
             Some(other) => {
-                let Bot { low, high, .. } = self.bots[&transfer.bot];
+                let Bot { id, low, high } = self.bots[&transfer.bot];
                 let mut new_sendings = Vec::new();
+                self.events.push(Event::BotCompared {
+                    bot: id,
+                    low: min(transfer.value, other),
+                    high: max(transfer.value, other),
+                });
 
                 let mut process = |receiver, val| match receiver {
                     Receiver::Bot(bot_id) => new_sendings.push(Transfer {
@@ -71,6 +229,11 @@ impl Factory {
                     }),
                     Receiver::Output(output_id) => {
                         self.output.insert(output_id, val);
+                        self.events.push(Event::ValueToOutput {
+                            bot: id,
+                            value: val,
+                            output: output_id,
+                        });
                     }
                 };
 
@@ -83,6 +246,191 @@ impl Factory {
     }
 }
 
+#[derive(Debug, PartialEq)]
+enum SolveError {
+    DuplicateRule { bot: u32 },
+    MissingRule { bot: u32 },
+    TooManyChips { bot: u32, chips: usize },
+    // One cycle of the wiring, starting from its lowest bot.
+    Cycle { bots: Vec<u32> },
+}
+
+impl std::fmt::Display for SolveError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SolveError::DuplicateRule { bot } => write!(f, "Bot {} has several rules", bot),
+            SolveError::MissingRule { bot } => {
+                write!(f, "Bot {} receives chips but has no rule", bot)
+            }
+            SolveError::TooManyChips { bot, chips } => {
+                write!(f, "Bot {} can receive {} chips, at most 2 fit", bot, chips)
+            }
+            SolveError::Cycle { bots } => {
+                let bots: Vec<String> = bots.iter().map(|b| format!("bot {}", b)).collect();
+                write!(f, "Wiring cycle: {}", bots.join(" -> "))
+            }
+        }
+    }
+}
+
+impl std::error::Error for SolveError {}
+
+#[derive(Debug)]
+struct Solution {
+    events: Vec<Event>,
+    outputs: BTreeMap<u32, Vec<u32>>,
+    // Bots left with fewer than two chips, and outputs that only they feed.
+    idle_bots: Vec<u32>,
+    unreachable_outputs: Vec<u32>,
+}
+
+impl Solution {
+    fn comparer(&self, a: u32, b: u32) -> Option<u32> {
+        comparer(&self.events, a, b)
+    }
+}
+
+// Alternative to `Factory::run` that checks the wiring up front and then
+// lets every bot act once, in topological order of the bot graph.
+fn solve(bots: &[Bot], transfers: &[Transfer]) -> Result<Solution, SolveError> {
+    let mut rules: BTreeMap<u32, Bot> = BTreeMap::new();
+    for bot in bots {
+        if rules.insert(bot.id, *bot).is_some() {
+            return Err(SolveError::DuplicateRule { bot: bot.id });
+        }
+    }
+    let mut senders: BTreeMap<u32, Vec<u32>> = rules.keys().map(|&id| (id, Vec::new())).collect();
+    let mut chips: BTreeMap<u32, usize> = BTreeMap::new();
+    for transfer in transfers {
+        *chips.entry(transfer.bot).or_default() += 1;
+    }
+    for bot in rules.values() {
+        for receiver in [bot.low, bot.high] {
+            if let Receiver::Bot(to) = receiver {
+                *chips.entry(to).or_default() += 1;
+                senders.entry(to).or_default().push(bot.id);
+            }
+        }
+    }
+    for (&bot, &count) in &chips {
+        if !rules.contains_key(&bot) {
+            return Err(SolveError::MissingRule { bot });
+        }
+        if count > 2 {
+            return Err(SolveError::TooManyChips { bot, chips: count });
+        }
+    }
+
+    // Kahn's algorithm: a bot is ready once every bot wired to it is done.
+    let mut pending: BTreeMap<u32, usize> = senders.iter().map(|(&id, s)| (id, s.len())).collect();
+    let mut ready: VecDeque<u32> = pending
+        .iter()
+        .filter(|&(_, &count)| count == 0)
+        .map(|(&id, _)| id)
+        .collect();
+    let mut order = Vec::with_capacity(rules.len());
+    while let Some(id) = ready.pop_front() {
+        order.push(id);
+        for receiver in [rules[&id].low, rules[&id].high] {
+            if let Receiver::Bot(to) = receiver {
+                let count = pending.get_mut(&to).unwrap();
+                *count -= 1;
+                if *count == 0 {
+                    ready.push_back(to);
+                }
+            }
+        }
+    }
+    if order.len() < rules.len() {
+        return Err(SolveError::Cycle {
+            bots: find_cycle(&senders, &order),
+        });
+    }
+
+    let mut holding: HashMap<u32, Vec<u32>> = HashMap::new();
+    let mut events = Vec::new();
+    for transfer in transfers {
+        holding
+            .entry(transfer.bot)
+            .or_default()
+            .push(transfer.value);
+        events.push(Event::ValueToBot {
+            value: transfer.value,
+            bot: transfer.bot,
+        });
+    }
+    let mut outputs: BTreeMap<u32, Vec<u32>> = BTreeMap::new();
+    let mut idle_bots = Vec::new();
+    let mut idle_outputs = BTreeSet::new();
+    for id in order {
+        let Bot { low, high, .. } = rules[&id];
+        let held = holding.remove(&id).unwrap_or_default();
+        if held.len() < 2 {
+            idle_bots.push(id);
+            for receiver in [low, high] {
+                if let Receiver::Output(output) = receiver {
+                    idle_outputs.insert(output);
+                }
+            }
+            continue;
+        }
+        let (a, b) = (held[0], held[1]);
+        events.push(Event::BotCompared {
+            bot: id,
+            low: min(a, b),
+            high: max(a, b),
+        });
+        for (receiver, value) in [(low, min(a, b)), (high, max(a, b))] {
+            match receiver {
+                Receiver::Bot(to) => holding.entry(to).or_default().push(value),
+                Receiver::Output(output) => {
+                    outputs.entry(output).or_default().push(value);
+                    events.push(Event::ValueToOutput {
+                        bot: id,
+                        value,
+                        output,
+                    });
+                }
+            }
+        }
+    }
+    idle_bots.sort_unstable();
+    let unreachable_outputs = idle_outputs
+        .into_iter()
+        .filter(|output| !outputs.contains_key(output))
+        .collect();
+    Ok(Solution {
+        events,
+        outputs,
+        idle_bots,
+        unreachable_outputs,
+    })
+}
+
+// Every bot left out of the topological order has a sender also left out, so
+// walking senders backwards from any of them must loop.
+fn find_cycle(senders: &BTreeMap<u32, Vec<u32>>, order: &[u32]) -> Vec<u32> {
+    let done: BTreeSet<u32> = order.iter().copied().collect();
+    let left = |id: &u32| !done.contains(id);
+    let mut path = vec![*senders.keys().find(|id| left(id)).unwrap()];
+    loop {
+        let current = *path.last().unwrap();
+        let previous = *senders[&current].iter().find(|id| left(id)).unwrap();
+        if let Some(start) = path.iter().position(|&id| id == previous) {
+            let mut cycle: Vec<u32> = path[start..].iter().rev().copied().collect();
+            let lowest = cycle
+                .iter()
+                .enumerate()
+                .min_by_key(|&(_, id)| id)
+                .unwrap()
+                .0;
+            cycle.rotate_left(lowest);
+            return cycle;
+        }
+        path.push(previous);
+    }
+}
+
 fn parse_input<T: AsRef<str>>(lines: &[T]) -> (Vec<Bot>, Vec<Transfer>) {
     let mut bots = Vec::new();
     let mut transfers = Vec::new();
@@ -206,11 +554,177 @@ mod tests {
         assert_eq!(part1, Some(2));
     }
 
+    fn example_factory() -> Factory {
+        let input = [
+            "value 5 goes to bot 2",
+            "bot 2 gives low to bot 1 and high to bot 0",
+            "value 3 goes to bot 1",
+            "bot 1 gives low to output 1 and high to bot 0",
+            "bot 0 gives low to output 2 and high to output 0",
+            "value 2 goes to bot 2",
+        ];
+        let (bots, transfers) = parse_input(&input);
+        let mut factory = Factory::new(&bots);
+        factory.run(&transfers);
+        factory
+    }
+
+    #[test]
+    fn test_events() {
+        let factory = example_factory();
+        assert_eq!(
+            factory.events,
+            [
+                Event::ValueToBot { value: 5, bot: 2 },
+                Event::ValueToBot { value: 3, bot: 1 },
+                Event::ValueToBot { value: 2, bot: 2 },
+                Event::BotCompared {
+                    bot: 2,
+                    low: 2,
+                    high: 5
+                },
+                Event::BotCompared {
+                    bot: 1,
+                    low: 2,
+                    high: 3
+                },
+                Event::ValueToOutput {
+                    bot: 1,
+                    value: 2,
+                    output: 1
+                },
+                Event::BotCompared {
+                    bot: 0,
+                    low: 3,
+                    high: 5
+                },
+                Event::ValueToOutput {
+                    bot: 0,
+                    value: 3,
+                    output: 2
+                },
+                Event::ValueToOutput {
+                    bot: 0,
+                    value: 5,
+                    output: 0
+                },
+            ]
+        );
+        assert_eq!(factory.comparer(5, 3), Some(0));
+        assert_eq!(factory.comparer(2, 3), Some(1));
+        assert_eq!(factory.comparer(2, 3), factory.comparer(3, 2));
+        assert_eq!(factory.comparer(5, 6), None);
+
+        // Bot 2 gets both of its chips before value 3 is delivered.
+        let bots: Vec<Bot> = factory.bots.values().copied().collect();
+        let mut factory = Factory::new(&bots);
+        factory.run(&[
+            Transfer { value: 5, bot: 2 },
+            Transfer { value: 2, bot: 2 },
+            Transfer { value: 3, bot: 1 },
+        ]);
+        assert_eq!(
+            factory.events[..4],
+            [
+                Event::ValueToBot { value: 5, bot: 2 },
+                Event::ValueToBot { value: 2, bot: 2 },
+                Event::BotCompared {
+                    bot: 2,
+                    low: 2,
+                    high: 5
+                },
+                Event::ValueToBot { value: 3, bot: 1 },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_dot() {
+        let dot = example_factory().to_dot();
+        assert!(dot.starts_with("digraph factory {\n"));
+        assert!(dot.contains("  \"bot 2\" -> \"bot 1\" [label=\"low: 2\"];\n"));
+        assert!(dot.contains("  \"bot 0\" -> \"output 0\" [label=\"high: 5\"];\n"));
+        assert!(dot.contains("  \"output 2\" [shape=box];\n"));
+        assert!(dot.contains("  \"value 3\" -> \"bot 1\";\n"));
+        assert!(dot.ends_with("}\n"));
+    }
+
+    #[test]
+    fn test_solve() {
+        let factory = example_factory();
+        let bots: Vec<Bot> = factory.bots.values().copied().collect();
+        let transfers = [
+            Transfer { value: 5, bot: 2 },
+            Transfer { value: 3, bot: 1 },
+            Transfer { value: 2, bot: 2 },
+        ];
+        let solution = solve(&bots, &transfers).unwrap();
+        assert_eq!(solution.comparer(2, 5), Some(2));
+        assert_eq!(solution.comparer(3, 5), Some(0));
+        assert_eq!(solution.outputs[&0], [5]);
+        assert_eq!(solution.outputs[&1], [2]);
+        assert!(solution.idle_bots.is_empty());
+        let compared = |events: &[Event]| {
+            let mut compared: Vec<Event> = events
+                .iter()
+                .filter(|e| matches!(e, Event::BotCompared { .. }))
+                .copied()
+                .collect();
+            compared.sort_by_key(|e| format!("{}", e));
+            compared
+        };
+        assert_eq!(compared(&solution.events), compared(&factory.events));
+
+        let solution = solve(&bots, &transfers[..2]).unwrap();
+        assert_eq!(solution.idle_bots, [0, 1, 2]);
+        assert_eq!(solution.unreachable_outputs, [0, 1, 2]);
+    }
+
+    #[test]
+    fn test_solve_errors() {
+        let solve_lines = |lines: &[&str]| {
+            let (bots, transfers) = parse_input(lines);
+            solve(&bots, &transfers).unwrap_err()
+        };
+        assert_eq!(
+            solve_lines(&["value 1 goes to bot 3"]),
+            SolveError::MissingRule { bot: 3 }
+        );
+        assert_eq!(
+            solve_lines(&[
+                "bot 1 gives low to output 0 and high to output 1",
+                "bot 1 gives low to output 1 and high to output 0",
+            ]),
+            SolveError::DuplicateRule { bot: 1 }
+        );
+        assert_eq!(
+            solve_lines(&[
+                "value 1 goes to bot 2",
+                "bot 1 gives low to bot 2 and high to bot 2",
+                "bot 2 gives low to output 0 and high to output 1",
+            ]),
+            SolveError::TooManyChips { bot: 2, chips: 3 }
+        );
+        assert_eq!(
+            solve_lines(&[
+                "bot 3 gives low to bot 1 and high to output 0",
+                "bot 1 gives low to bot 2 and high to output 1",
+                "bot 2 gives low to bot 3 and high to bot 4",
+                "bot 4 gives low to output 2 and high to output 3",
+            ]),
+            SolveError::Cycle {
+                bots: vec![1, 2, 3]
+            }
+        );
+    }
+
     #[test]
     fn test_parts() {
         let mut factory = Factory::new(&PARSED_INPUT.0);
         let part1 = part1(&mut factory, &PARSED_INPUT.1, 17, 61);
         assert_eq!(part1, Some(98));
         assert_eq!(part2(&factory), 4042);
+        let solution = solve(&PARSED_INPUT.0, &PARSED_INPUT.1).unwrap();
+        assert_eq!(solution.comparer(17, 61), Some(98));
     }
 }
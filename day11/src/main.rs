@@ -1,55 +1,311 @@
 use itertools::Itertools;
 use once_cell::sync::Lazy;
-use std::collections::{HashMap, HashSet, VecDeque};
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashMap, HashSet, VecDeque};
+use std::fmt;
 use tinybitset::TinyBitSet;
 
-// In both the example and the input, 8 bits are enough
-type Set = TinyBitSet<u8, 1>;
+// One bit per element type
+type Set = TinyBitSet<u64, 1>;
 
-static PART1: Lazy<Configuration> = Lazy::new(|| {
+const MAX_ELEMENTS: usize = 64;
+
+// Elevator floor and sorted (microchip floor, generator floor) pairs.
+type Key = (usize, Vec<(Option<usize>, Option<usize>)>);
+
+static PART1: Lazy<Facility> = Lazy::new(|| {
     let input = common::read_file_as_lines("data/day11.txt").unwrap();
-    parse_input(&input)
+    Facility::parse(&input).unwrap()
 });
 
-static PART2: Lazy<Configuration> = Lazy::new(|| {
-    // The input has only 5 different generators and microchips
+static PART2: Lazy<Facility> = Lazy::new(|| {
     let mut part2 = PART1.clone();
-    part2.floors[0].generators.insert(6);
-    part2.floors[0].generators.insert(7);
-    part2.floors[0].microchips.insert(6);
-    part2.floors[0].microchips.insert(7);
+    part2.add_element("elerium", 0).unwrap();
+    part2.add_element("dilithium", 0).unwrap();
     part2
 });
 
 fn main() {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let value = |name: &str| {
+        args.iter()
+            .position(|arg| arg == name)
+            .map(|i| args.get(i + 1).expect("Missing option value"))
+    };
+    if args.iter().any(|arg| arg == "--compare") {
+        for (label, facility) in [("Part 1", &*PART1), ("Part 2", &*PART2)] {
+            for search in [Search::Bfs, Search::AStar] {
+                let start = std::time::Instant::now();
+                let result = facility.search(search);
+                println!(
+                    "{} {:?}: {:?} moves, {} expanded in {:?}",
+                    label,
+                    search,
+                    result.moves.map(|m| m.len()),
+                    result.expanded,
+                    start.elapsed()
+                );
+            }
+        }
+        return;
+    }
+    if args.iter().any(|arg| arg == "--moves") {
+        let mut facility = PART1.clone();
+        if let Some(capacity) = value("--capacity") {
+            facility.capacity = capacity.parse().unwrap();
+        }
+        if let Some(names) = value("--add") {
+            for name in names.split(',') {
+                facility.add_element(name, 0).unwrap();
+            }
+        }
+        match facility.solve() {
+            Some(moves) => {
+                for (i, step) in moves.iter().enumerate() {
+                    println!("{:3}: {}", i + 1, facility.describe(step));
+                }
+            }
+            None => println!("No solution"),
+        }
+        return;
+    }
     println!("Part 1: {}", part(&PART1));
     println!("Part 2: {}", part(&PART2));
 }
 
-fn part(initial: &Configuration) -> usize {
-    let mut visited = HashSet::new();
-    let mut queue = VecDeque::new();
-    queue.push_back((0, *initial));
-    visited.insert(initial.normalize());
-    while let Some((depth, configuration)) = queue.pop_front() {
-        let expanded = configuration.expand();
-        for configuration in expanded {
-            let normalized = configuration.normalize();
-            if configuration.is_solution() {
-                return depth + 1;
-            } else if !visited.contains(&normalized) {
-                queue.push_back((depth + 1, configuration));
-                visited.insert(normalized);
+fn part(facility: &Facility) -> usize {
+    facility.solve().unwrap().len()
+}
+
+#[derive(Debug, PartialEq)]
+enum FacilityError {
+    NoFloors,
+    TooManyElements,
+    NoSuchFloor(usize),
+    DuplicateElement(String),
+}
+
+impl fmt::Display for FacilityError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FacilityError::NoFloors => write!(f, "The facility has no floors"),
+            FacilityError::TooManyElements => {
+                write!(f, "At most {} element types are supported", MAX_ELEMENTS)
+            }
+            FacilityError::NoSuchFloor(floor) => write!(f, "There is no floor {}", floor),
+            FacilityError::DuplicateElement(name) => {
+                write!(f, "Element {} is already in the facility", name)
             }
         }
     }
-    unreachable!()
 }
 
-#[derive(Debug, Clone, Copy, Eq, PartialEq)]
-enum Direction {
-    Up,
-    Down,
+impl std::error::Error for FacilityError {}
+
+// The floors and element names of a facility, and how many items the
+// elevator carries at once (it never moves empty).
+#[derive(Debug, Clone)]
+struct Facility {
+    names: Vec<String>,
+    capacity: usize,
+    initial: Configuration,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Search {
+    Bfs,
+    AStar,
+}
+
+#[derive(Debug)]
+struct SearchResult {
+    moves: Option<Vec<Move>>,
+    expanded: usize,
+}
+
+// A configuration reached during a search, with the node it was reached
+// from and the move taken.
+type Node = (Configuration, Option<(usize, Move)>);
+
+// The elevator going from `from` to `to` with the items of `group`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Move {
+    from: usize,
+    to: usize,
+    group: Group,
+}
+
+impl Facility {
+    // One floor per line, from the bottom one.
+    fn parse<T: AsRef<str>>(input: &[T]) -> Result<Self, FacilityError> {
+        static MICROCHIP_REGEX: Lazy<regex::Regex> =
+            Lazy::new(|| regex::Regex::new(r"(\w+)-compatible microchip").unwrap());
+        static GENERATOR_REGEX: Lazy<regex::Regex> =
+            Lazy::new(|| regex::Regex::new(r"(\w+) generator").unwrap());
+
+        if input.is_empty() {
+            return Err(FacilityError::NoFloors);
+        }
+        let mut facility = Self {
+            names: Vec::new(),
+            capacity: 2,
+            initial: Configuration {
+                elevator: 0,
+                floors: vec![Group::default(); input.len()],
+            },
+        };
+        for (i, line) in input.iter().enumerate() {
+            for microchip in MICROCHIP_REGEX.captures_iter(line.as_ref()) {
+                let id = facility.id(&microchip[1])?;
+                facility.initial.floors[i].microchips.insert(id);
+            }
+            for generator in GENERATOR_REGEX.captures_iter(line.as_ref()) {
+                let id = facility.id(&generator[1])?;
+                facility.initial.floors[i].generators.insert(id);
+            }
+        }
+        Ok(facility)
+    }
+
+    fn id(&mut self, name: &str) -> Result<usize, FacilityError> {
+        if let Some(id) = self.names.iter().position(|n| n == name) {
+            return Ok(id);
+        }
+        if self.names.len() == MAX_ELEMENTS {
+            return Err(FacilityError::TooManyElements);
+        }
+        self.names.push(name.to_string());
+        Ok(self.names.len() - 1)
+    }
+
+    // Adds both the generator and the microchip of a new element on `floor`.
+    fn add_element(&mut self, name: &str, floor: usize) -> Result<(), FacilityError> {
+        if floor >= self.initial.floors.len() {
+            return Err(FacilityError::NoSuchFloor(floor));
+        }
+        if self.names.iter().any(|n| n == name) {
+            return Err(FacilityError::DuplicateElement(name.to_string()));
+        }
+        let id = self.id(name)?;
+        self.initial.floors[floor].microchips.insert(id);
+        self.initial.floors[floor].generators.insert(id);
+        Ok(())
+    }
+
+    // Shortest sequence of moves bringing everything to the top floor.
+    fn solve(&self) -> Option<Vec<Move>> {
+        self.search(Search::AStar).moves
+    }
+
+    // Both searches run over configurations equivalent up to renaming the
+    // elements, and count every configuration they expand.
+    fn search(&self, search: Search) -> SearchResult {
+        match search {
+            Search::Bfs => self.bfs(),
+            Search::AStar => self.astar(),
+        }
+    }
+
+    fn bfs(&self) -> SearchResult {
+        let mut nodes: Vec<Node> = Vec::new();
+        let mut visited = HashSet::new();
+        let mut queue = VecDeque::new();
+        nodes.push((self.initial.clone(), None));
+        visited.insert(self.initial.normalize());
+        queue.push_back(0);
+        let mut expanded = 0;
+        while let Some(index) = queue.pop_front() {
+            let configuration = &nodes[index].0;
+            if configuration.is_solution() {
+                return SearchResult {
+                    moves: Some(Self::moves(&nodes, index)),
+                    expanded,
+                };
+            }
+            expanded += 1;
+            for (step, next) in configuration.expand(self.capacity) {
+                if visited.insert(next.normalize()) {
+                    queue.push_back(nodes.len());
+                    nodes.push((next, Some((index, step))));
+                }
+            }
+        }
+        SearchResult {
+            moves: None,
+            expanded,
+        }
+    }
+
+    fn astar(&self) -> SearchResult {
+        let mut nodes: Vec<Node> = Vec::new();
+        let mut best: HashMap<Key, usize> = HashMap::new();
+        let mut heap = BinaryHeap::new();
+        nodes.push((self.initial.clone(), None));
+        best.insert(self.initial.normalize(), 0);
+        if let Some(estimate) = self.initial.heuristic(self.capacity) {
+            heap.push(Reverse((estimate, 0, 0)));
+        }
+        let mut expanded = 0;
+        while let Some(Reverse((_, cost, index))) = heap.pop() {
+            let configuration = &nodes[index].0;
+            // Skip entries superseded by a cheaper path to the same configuration.
+            if best[&configuration.normalize()] < cost {
+                continue;
+            }
+            if configuration.is_solution() {
+                return SearchResult {
+                    moves: Some(Self::moves(&nodes, index)),
+                    expanded,
+                };
+            }
+            expanded += 1;
+            for (step, next) in configuration.expand(self.capacity) {
+                // Configurations that cannot lead to a solution are dropped.
+                let Some(remaining) = next.heuristic(self.capacity) else {
+                    continue;
+                };
+                let known = best.entry(next.normalize()).or_insert(usize::MAX);
+                if cost + 1 < *known {
+                    *known = cost + 1;
+                    heap.push(Reverse((cost + 1 + remaining, cost + 1, nodes.len())));
+                    nodes.push((next, Some((index, step))));
+                }
+            }
+        }
+        SearchResult {
+            moves: None,
+            expanded,
+        }
+    }
+
+    fn moves(nodes: &[Node], mut index: usize) -> Vec<Move> {
+        let mut moves = Vec::new();
+        while let Some((parent, step)) = nodes[index].1 {
+            moves.push(step);
+            index = parent;
+        }
+        moves.reverse();
+        moves
+    }
+
+    fn describe(&self, step: &Move) -> String {
+        let items = itertools::chain!(
+            step.group
+                .microchips
+                .iter()
+                .map(|id| format!("{} microchip", self.names[id])),
+            step.group
+                .generators
+                .iter()
+                .map(|id| format!("{} generator", self.names[id])),
+        );
+        format!(
+            "floor {} -> {}: {}",
+            step.from + 1,
+            step.to + 1,
+            items.format(", ")
+        )
+    }
 }
 
 #[derive(Debug, Clone, Copy, Eq, PartialEq, Hash, Default)]
@@ -59,13 +315,6 @@ struct Group {
 }
 
 impl Group {
-    fn new(microchips: Set, generators: Set) -> Self {
-        Self {
-            microchips,
-            generators,
-        }
-    }
-
     fn is_empty(&self) -> bool {
         self.microchips.is_empty() && self.generators.is_empty()
     }
@@ -89,145 +338,260 @@ impl Group {
     }
 }
 
-#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
 struct Configuration {
     elevator: usize,
-    floors: [Group; 4],
+    floors: Vec<Group>,
 }
 
 impl Configuration {
+    fn top(&self) -> usize {
+        self.floors.len() - 1
+    }
+
     fn is_solution(&self) -> bool {
-        self.elevator == 3 && (0..3).all(|i| self.floors[i].is_empty())
+        self.elevator == self.top() && self.floors[..self.top()].iter().all(Group::is_empty)
+    }
+
+    // Lower bound on the moves left, or None when the top floor can no longer
+    // be reached. Every move crosses exactly one boundary between two floors,
+    // so the bounds for each boundary add up. Below a boundary holding c items,
+    // every trip up takes at most `capacity` of them across and every trip
+    // down brings at least one back, so it takes at least
+    // ceil((c - 1) / (capacity - 1)) trips up, and one fewer down unless the
+    // elevator starts above it. With a capacity of one, no more than a single
+    // item can ever cross. This is never worse than moving every item one
+    // floor per move at full capacity. Carrying several items up can lower
+    // the bound by more than one, so A* may have to reopen configurations.
+    fn heuristic(&self, capacity: usize) -> Option<usize> {
+        let mut below = 0;
+        let mut distance = 0;
+        let mut crossings = 0;
+        for (boundary, group) in self.floors[..self.top()].iter().enumerate() {
+            below += group.microchips.len() + group.generators.len();
+            distance += below;
+            let elevator_above = self.elevator > boundary;
+            crossings += match below {
+                0 if elevator_above => 0,
+                0 => 1,
+                _ => {
+                    let ups = if below <= capacity {
+                        1
+                    } else if capacity <= 1 {
+                        return None;
+                    } else {
+                        (below - 1).div_ceil(capacity - 1)
+                    };
+                    if elevator_above { 2 * ups } else { 2 * ups - 1 }
+                }
+            };
+        }
+        Some(crossings.max(distance.div_ceil(capacity.max(1))))
     }
 
-    fn valid_groups(&self) -> impl Iterator<Item = Group> + '_ {
+    // Every set of at most `capacity` items of the current floor that can
+    // share the elevator.
+    fn valid_groups(&self, capacity: usize) -> Vec<Group> {
         let floor = self.floors[self.elevator];
-        itertools::chain!(
-            floor
-                .microchips
-                .iter()
-                .map(|m| Group::new(Set::singleton(m), Set::new())),
-            floor
-                .generators
-                .iter()
-                .map(|g| Group::new(Set::new(), Set::singleton(g))),
-            floor
-                .microchips
-                .iter()
-                .tuple_combinations()
-                .map(|(m1, m2)| Group::new(Set::singleton(m1) | Set::singleton(m2), Set::new())),
-            floor
-                .generators
-                .iter()
-                .tuple_combinations()
-                .map(|(g1, g2)| Group::new(Set::new(), Set::singleton(g1) | Set::singleton(g2))),
-            (floor.microchips & floor.generators)
-                .iter()
-                .map(|mg| Group::new(Set::singleton(mg), Set::singleton(mg)))
-        )
+        let items: Vec<(bool, usize)> = floor
+            .microchips
+            .iter()
+            .map(|m| (false, m))
+            .chain(floor.generators.iter().map(|g| (true, g)))
+            .collect();
+        let mut groups = Vec::new();
+        for size in 1..=capacity.min(items.len()) {
+            for combination in items.iter().combinations(size) {
+                let mut group = Group::default();
+                for &(is_generator, id) in combination {
+                    if is_generator {
+                        group.generators.insert(id);
+                    } else {
+                        group.microchips.insert(id);
+                    }
+                }
+                if group.is_valid() {
+                    groups.push(group);
+                }
+            }
+        }
+        groups
     }
 
-    fn expand(&self) -> Vec<Self> {
+    fn expand(&self, capacity: usize) -> Vec<(Move, Self)> {
         let mut configurations = Vec::new();
         let current_floor = self.elevator;
         let current_group = &self.floors[current_floor];
-        for group in self.valid_groups() {
+        for group in self.valid_groups(capacity) {
             let new_current_group = current_group.remove(group);
             if !new_current_group.is_valid() {
                 continue;
             }
-            for direction in [Direction::Up, Direction::Down] {
-                let new_floor = match direction {
-                    Direction::Up => {
-                        if current_floor == 3 {
-                            continue;
-                        }
-                        current_floor + 1
-                    }
-                    Direction::Down => {
-                        if current_floor == 0
-                            || self.floors[..current_floor].iter().all(|f| f.is_empty())
-                        {
-                            continue;
-                        }
-                        current_floor - 1
-                    }
-                };
-                let new_floor_group = &self.floors[new_floor];
-                let new_floor_grup = new_floor_group.add(group);
-                if !new_floor_grup.is_valid() {
+            // Never bring items back down to floors that are already empty.
+            let up = (current_floor < self.top()).then(|| current_floor + 1);
+            let down = (current_floor > 0
+                && !self.floors[..current_floor].iter().all(Group::is_empty))
+            .then(|| current_floor - 1);
+            for new_floor in up.into_iter().chain(down) {
+                let new_floor_group = self.floors[new_floor].add(group);
+                if !new_floor_group.is_valid() {
                     continue;
                 }
-                let mut new_floors = self.floors;
+                let mut new_floors = self.floors.clone();
                 new_floors[current_floor] = new_current_group;
-                new_floors[new_floor] = new_floor_grup;
-                configurations.push(Self {
-                    elevator: new_floor,
-                    floors: new_floors,
-                });
+                new_floors[new_floor] = new_floor_group;
+                configurations.push((
+                    Move {
+                        from: current_floor,
+                        to: new_floor,
+                        group,
+                    },
+                    Self {
+                        elevator: new_floor,
+                        floors: new_floors,
+                    },
+                ));
             }
         }
         configurations
     }
 
-    fn normalize(&self) -> (usize, Vec<(usize, usize)>) {
-        // - For each possible element ID (0 to 7), it finds the floor index where
-        // its microchip and generator are located.
-        // - It collects these pairs (chip_floor, gen_floor) into a vector.
-        // - The vector is sorted to ensure that states with the same arrangement
-        // but different orderings are treated as equal.
-        // - It returns a tuple: the elevator position and the sorted vector of pairs.
-        // Thanks copilot :-D !!!
-        let mut pairs = Vec::new();
-        for id in 0..8 {
+    // Elements are interchangeable, so a configuration is identified by the
+    // elevator floor and the sorted (microchip floor, generator floor) pairs.
+    fn normalize(&self) -> Key {
+        let present = self
+            .floors
+            .iter()
+            .fold(Set::default(), |set, g| set | g.microchips | g.generators);
+        let mut pairs = Vec::with_capacity(present.len());
+        for id in present.iter() {
             let chip_floor = self.floors.iter().position(|g| g.microchips[id]);
             let gen_floor = self.floors.iter().position(|g| g.generators[id]);
-            if let (Some(c), Some(g)) = (chip_floor, gen_floor) {
-                pairs.push((c, g));
-            }
+            pairs.push((chip_floor, gen_floor));
         }
         pairs.sort_unstable();
         (self.elevator, pairs)
     }
 }
 
-fn parse_input<T: AsRef<str>>(input: &[T]) -> Configuration {
-    let mut names = HashMap::new();
-    let mut floors = [Group::default(); 4];
-    for (i, line) in input.iter().enumerate() {
-        let microchip_regex = regex::Regex::new(r"(\w+)-compatible microchip").unwrap();
-        let generator_regex = regex::Regex::new(r"(\w+) generator").unwrap();
-        for microchip in microchip_regex.captures_iter(line.as_ref()) {
-            let name = microchip[1].to_string();
-            let num_names = names.len();
-            let id = names.entry(name).or_insert_with(|| num_names);
-            floors[i].microchips.insert(*id);
-        }
-        for generator in generator_regex.captures_iter(line.as_ref()) {
-            let name = generator[1].to_string();
-            let num_names = names.len();
-            let id = names.entry(name).or_insert_with(|| num_names);
-            floors[i].generators.insert(*id);
-        }
-    }
-    Configuration {
-        elevator: 0,
-        floors,
-    }
-}
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    const EXAMPLE: [&str; 4] = [
+        "The first floor contains a hydrogen-compatible microchip and a lithium-compatible microchip.",
+        "The second floor contains a hydrogen generator.",
+        "The third floor contains a lithium generator.",
+        "The fourth floor contains nothing relevant.",
+    ];
+
+    // Replays the moves from the initial configuration, checking each one.
+    fn replay(facility: &Facility, moves: &[Move]) -> Configuration {
+        let mut configuration = facility.initial.clone();
+        for step in moves {
+            let (_, next) = configuration
+                .expand(facility.capacity)
+                .into_iter()
+                .find(|(m, _)| m == step)
+                .expect("Invalid move");
+            configuration = next;
+        }
+        configuration
+    }
+
     #[test]
     fn test_example_part1() {
-        let example = vec![
-            "The first floor contains a hydrogen-compatible microchip and a lithium-compatible microchip.",
-            "The second floor contains a hydrogen generator.",
-            "The third floor contains a lithium generator.",
-            "The fourth floor contains nothing relevant.",
-        ];
-        assert_eq!(part(&parse_input(&example)), 11);
+        let facility = Facility::parse(&EXAMPLE).unwrap();
+        assert_eq!(part(&facility), 11);
+        let moves = facility.solve().unwrap();
+        assert!(replay(&facility, &moves).is_solution());
+        assert_eq!(
+            facility.describe(&moves[0]),
+            "floor 1 -> 2: hydrogen microchip"
+        );
+    }
+
+    #[test]
+    fn test_configurable_facility() {
+        let facility = Facility::parse(&EXAMPLE[..3]).unwrap();
+        assert_eq!(part(&facility), 6);
+
+        let mut facility = Facility::parse(&EXAMPLE).unwrap();
+        facility.capacity = 1;
+        assert_eq!(facility.solve(), None);
+        facility.capacity = 3;
+        let moves = facility.solve().unwrap();
+        assert_eq!(moves.len(), 9);
+        assert!(replay(&facility, &moves).is_solution());
+
+        let mut facility = Facility::parse(&EXAMPLE).unwrap();
+        facility.add_element("elerium", 0).unwrap();
+        let moves = facility.solve().unwrap();
+        assert!(replay(&facility, &moves).is_solution());
+        assert_eq!(
+            facility.add_element("lithium", 0),
+            Err(FacilityError::DuplicateElement("lithium".to_string()))
+        );
+        assert_eq!(
+            facility.add_element("plutonium", 4),
+            Err(FacilityError::NoSuchFloor(4))
+        );
+    }
+
+    #[test]
+    fn test_many_elements() {
+        let mut facility = Facility::parse(&["", ""]).unwrap();
+        for i in 0..MAX_ELEMENTS - 1 {
+            facility.add_element(&format!("e{}", i), 1).unwrap();
+        }
+        facility.add_element("last", 0).unwrap();
+        let moves = facility.solve().unwrap();
+        assert_eq!(moves.len(), 1);
+        assert_eq!(
+            facility.describe(&moves[0]),
+            "floor 1 -> 2: last microchip, last generator"
+        );
+        assert_eq!(
+            facility.add_element("one-too-many", 0),
+            Err(FacilityError::TooManyElements)
+        );
+        let empty: [&str; 0] = [];
+        assert_eq!(
+            Facility::parse(&empty).unwrap_err(),
+            FacilityError::NoFloors
+        );
+    }
+
+    #[test]
+    fn test_astar_matches_bfs() {
+        let mut facility = Facility::parse(&EXAMPLE).unwrap();
+        facility.add_element("elerium", 1).unwrap();
+        for capacity in 1..=3 {
+            facility.capacity = capacity;
+            let bfs = facility.search(Search::Bfs);
+            let astar = facility.search(Search::AStar);
+            assert_eq!(
+                astar.moves.as_ref().map(Vec::len),
+                bfs.moves.as_ref().map(Vec::len)
+            );
+            assert!(astar.expanded <= bfs.expanded);
+            if let Some(moves) = &astar.moves {
+                assert!(replay(&facility, moves).is_solution());
+            }
+        }
+        assert!(facility.initial.heuristic(3).unwrap() <= facility.solve().unwrap().len());
+        assert_eq!(facility.initial.heuristic(1), None);
+    }
+
+    #[test]
+    fn test_astar_expands_less() {
+        let mut facility = Facility::parse(&EXAMPLE).unwrap();
+        facility.add_element("elerium", 1).unwrap();
+        facility.add_element("dilithium", 2).unwrap();
+        let bfs = facility.search(Search::Bfs);
+        let astar = facility.search(Search::AStar);
+        assert_eq!(astar.moves.unwrap().len(), bfs.moves.unwrap().len());
+        assert!(astar.expanded * 4 < bfs.expanded);
     }
 
     #[test]
@@ -1,62 +1,147 @@
+mod md5x;
+mod store;
+
 use md5::{Digest, Md5};
 use once_cell::sync::Lazy;
+use std::env;
+use std::path::{Path, PathBuf};
+use store::Store;
 
 static SALT: Lazy<String> = Lazy::new(|| common::read_file_as_string("data/day14.txt").unwrap());
 
 fn main() {
-    println!("Part 1: {}", part1(&SALT));
-    println!("Part 1: {}", part2(&SALT));
+    let cache_dir = env::var_os("DAY14_CACHE_DIR").map(PathBuf::from);
+    if env::args().any(|arg| arg == "--keys") {
+        println!("Part 1 keys:");
+        let cache = Cache::new(&SALT, |buffer: &[u8]| Md5::digest(buffer).into());
+        print_keys(cache.persisted(cache_dir.as_deref(), 0));
+        println!("Part 2 keys:");
+        let cache = Cache::new(&SALT, StretchedMd5);
+        print_keys(cache.persisted(cache_dir.as_deref(), STRETCH));
+        return;
+    }
+    println!("Part 1: {}", part1(&SALT, cache_dir.as_deref()));
+    println!("Part 2: {}", part2(&SALT, cache_dir.as_deref()));
 }
 
-fn part1(salt: &str) -> usize {
-    let cache = Cache::new(salt, |buffer| Md5::digest(buffer).into());
-    part(cache)
+fn part1(salt: &str, cache_dir: Option<&Path>) -> usize {
+    let cache = Cache::new(salt, |buffer: &[u8]| Md5::digest(buffer).into());
+    part(cache.persisted(cache_dir, 0))
 }
 
-fn part2(salt: &str) -> usize {
-    let cache = Cache::new(salt, |buffer| stretched_hash(buffer).into());
-    part(cache)
+fn part2(salt: &str, cache_dir: Option<&Path>) -> usize {
+    let cache = Cache::new(salt, StretchedMd5);
+    part(cache.persisted(cache_dir, STRETCH))
 }
 
-fn part<F>(cache: Cache<F>) -> usize
+fn part<H>(cache: Cache<H>) -> usize
 where
-    F: Fn(&[u8]) -> [u8; 16],
+    H: Hasher,
+{
+    let mut finder = KeyFinder::new(cache);
+    let key = finder.nth(63).unwrap();
+    finder.save_cache();
+    key.index
+}
+
+fn print_keys<H>(cache: Cache<H>)
+where
+    H: Hasher,
 {
     let mut finder = KeyFinder::new(cache);
-    let mut from = 0;
-    for _ in 0..64 {
-        from = finder.find_index(from);
-        from += 1;
+    for (n, key) in finder.by_ref().take(64).enumerate() {
+        println!(
+            "{:2}: index {:6}  triplet {:x}  confirmed at {:6} (+{:3})  {}",
+            n + 1,
+            key.index,
+            key.triplet_nibble,
+            key.confirming_index,
+            key.confirming_index - key.index,
+            key.hash_hex
+        );
     }
-    from - 1
+    finder.save_cache();
 }
 
 const CACHE_SIZE: usize = 1001;
+const PREFETCH: usize = md5x::LANES;
+// The window keeps the last CACHE_SIZE suffixes plus up to PREFETCH - 1 that
+// were hashed ahead of it, so batches at the front are never cut short.
+const SLOTS: usize = CACHE_SIZE + PREFETCH - 1;
+const STRETCH: u32 = 2016;
+
+trait Hasher {
+    fn hash_batch(&self, inputs: &[Vec<u8>]) -> Vec<[u8; 16]>;
+}
 
-struct Cache<F>
+impl<F> Hasher for F
 where
     F: Fn(&[u8]) -> [u8; 16],
 {
-    salt_length: usize,
-    salt_buffer: Vec<u8>,
-    f: F,
+    fn hash_batch(&self, inputs: &[Vec<u8>]) -> Vec<[u8; 16]> {
+        inputs.iter().map(|input| self(input)).collect()
+    }
+}
+
+struct StretchedMd5;
+
+impl Hasher for StretchedMd5 {
+    fn hash_batch(&self, inputs: &[Vec<u8>]) -> Vec<[u8; 16]> {
+        let mut result = Vec::with_capacity(inputs.len());
+        for chunk in inputs.chunks(md5x::LANES) {
+            let mut hashes = [[0u8; 16]; md5x::LANES];
+            for (hash, input) in hashes.iter_mut().zip(chunk) {
+                *hash = Md5::digest(input).into();
+            }
+            md5x::stretch(&mut hashes, STRETCH as usize);
+            result.extend_from_slice(&hashes[..chunk.len()]);
+        }
+        result
+    }
+}
+
+struct Cache<H>
+where
+    H: Hasher,
+{
+    salt: Vec<u8>,
+    hasher: H,
     entries: Vec<Option<CacheEntry>>,
     start_suffix: usize,
+    store: Option<Store>,
 }
 
-impl<F> Cache<F>
+impl<H> Cache<H>
 where
-    F: Fn(&[u8]) -> [u8; 16],
+    H: Hasher,
 {
-    fn new(salt: &str, f: F) -> Self {
-        let mut entries = Vec::with_capacity(CACHE_SIZE);
-        entries.resize_with(CACHE_SIZE, || None);
+    fn new(salt: &str, hasher: H) -> Self {
+        let mut entries = Vec::with_capacity(SLOTS);
+        entries.resize_with(SLOTS, || None);
         Self {
-            salt_length: salt.len(),
-            salt_buffer: salt.as_bytes().to_vec(),
-            f,
+            salt: salt.as_bytes().to_vec(),
+            hasher,
             entries,
             start_suffix: 0,
+            store: None,
+        }
+    }
+
+    fn persisted(mut self, dir: Option<&Path>, stretch: u32) -> Self {
+        if let Some(dir) = dir {
+            let salt = String::from_utf8_lossy(&self.salt).into_owned();
+            match Store::open(dir, &salt, stretch) {
+                Ok(store) => self.store = Some(store),
+                Err(e) => eprintln!("Unable to open the hash cache: {}", e),
+            }
+        }
+        self
+    }
+
+    fn save(&mut self) -> std::io::Result<()> {
+        match &mut self.store {
+            Some(store) => store.save(),
+            None => Ok(()),
         }
     }
 
@@ -65,33 +150,67 @@ where
             panic!("Requested suffix {} is before the buffer start {}", suffix, self.start_suffix);
         }
         if suffix >= self.start_suffix + CACHE_SIZE {
-            let idx = self.start_suffix % CACHE_SIZE;
+            let idx = self.start_suffix % SLOTS;
             self.entries[idx] = None;
             self.start_suffix += 1;
         }
-        let idx = suffix % CACHE_SIZE;
+        let idx = suffix % SLOTS;
         if self.entries[idx].is_none() {
-            self.salt_buffer.truncate(self.salt_length);
-            self.salt_buffer.extend_from_slice(&suffix.to_string().as_bytes());
-            let result = (self.f)(&self.salt_buffer);
-            self.entries[idx] = Some(CacheEntry::new(&result));
+            self.prefetch(suffix);
         }
         self.entries[idx].as_ref().unwrap()
     }
+
+    // Hashes the missing suffixes among the PREFETCH from `suffix` onwards in a
+    // single batch.
+    fn prefetch(&mut self, suffix: usize) {
+        let end = suffix + PREFETCH;
+        let mut suffixes = Vec::new();
+        for s in suffix..end {
+            if self.entries[s % SLOTS].is_some() {
+                continue;
+            }
+            match self.store.as_ref().and_then(|store| store.get(s)) {
+                Some(entry) => self.entries[s % SLOTS] = Some(entry),
+                None => suffixes.push(s),
+            }
+        }
+        if suffixes.is_empty() {
+            return;
+        }
+        let inputs: Vec<Vec<u8>> = suffixes
+            .iter()
+            .map(|s| {
+                let mut buffer = self.salt.clone();
+                buffer.extend_from_slice(s.to_string().as_bytes());
+                buffer
+            })
+            .collect();
+        let hashes = self.hasher.hash_batch(&inputs);
+        for (&s, hash) in suffixes.iter().zip(hashes) {
+            let entry = CacheEntry::new(hash);
+            if let Some(store) = &mut self.store {
+                store.record(s, entry);
+            }
+            self.entries[s % SLOTS] = Some(entry);
+        }
+    }
 }
 
+#[derive(Debug, Clone, Copy, PartialEq)]
 struct CacheEntry {
+    hash: [u8; 16],
     first_triplet: Option<u8>,
     quintuplets: u16,
 }
 
 impl CacheEntry {
-    fn new(hash: &[u8]) -> Self {
+    fn new(hash: [u8; 16]) -> Self {
         let mut first_triplet = None;
         let mut quintuplets = 0_u16;
         let mut prev = None;
         let mut count = 1;
-        for &byte in hash {
+        for &byte in &hash {
             for &nibble in &[byte >> 4, byte & 0x0F] {
                 if Some(nibble) == prev {
                     count += 1;
@@ -109,51 +228,95 @@ impl CacheEntry {
         }
 
         Self {
+            hash,
             first_triplet,
             quintuplets,
         }
     }
 }
 
-struct KeyFinder<F>
+#[derive(Debug, Clone, PartialEq)]
+struct Key {
+    index: usize,
+    triplet_nibble: u8,
+    confirming_index: usize,
+    hash_hex: String,
+}
+
+struct KeyFinder<H>
 where
-    F: Fn(&[u8]) -> [u8; 16],
+    H: Hasher,
 {
-    cache: Cache<F>,
+    cache: Cache<H>,
+    next_from: usize,
 }
 
-impl<F> KeyFinder<F>
+impl<H> KeyFinder<H>
 where
-    F: Fn(&[u8]) -> [u8; 16],
+    H: Hasher,
 {
-    fn new(cache: Cache<F>) -> Self {
-        Self { cache }
+    fn new(cache: Cache<H>) -> Self {
+        Self {
+            cache,
+            next_from: 0,
+        }
     }
 
+    #[cfg(test)]
     fn find_index(&mut self, from: usize) -> usize {
+        self.find_key(from).index
+    }
+
+    // Suffixes are only ever requested in increasing order (at most 1000 ahead
+    // of the candidate), so the cache window stays valid however lazily the
+    // keys are pulled.
+    fn find_key(&mut self, from: usize) -> Key {
         let mut suffix = from;
         loop {
-            let first_triplet = self.cache.apply(suffix).first_triplet;
-            if let Some(byte) = first_triplet {
-                if self.five_in_a_row_in_next_thousand(suffix + 1, byte) {
-                    return suffix;
-                }
+            let entry = *self.cache.apply(suffix);
+            if let Some(nibble) = entry.first_triplet
+                && let Some(confirming_index) =
+                    self.five_in_a_row_in_next_thousand(suffix + 1, nibble)
+            {
+                return Key {
+                    index: suffix,
+                    triplet_nibble: nibble,
+                    confirming_index,
+                    hash_hex: String::from_utf8(md5x::to_hex(&entry.hash).to_vec()).unwrap(),
+                };
             }
             suffix += 1;
         }
     }
 
-    fn five_in_a_row_in_next_thousand(&mut self, from: usize, byte: u8) -> bool {
-        for i in from..from + 1000 {
-            let quintuplets = &self.cache.apply(i).quintuplets;
-            if (quintuplets & (1 << (byte as u16))) != 0 {
-                return true;
-            }
+    fn five_in_a_row_in_next_thousand(&mut self, from: usize, nibble: u8) -> Option<usize> {
+        (from..from + 1000).find(|&i| {
+            let quintuplets = self.cache.apply(i).quintuplets;
+            (quintuplets & (1 << (nibble as u16))) != 0
+        })
+    }
+
+    fn save_cache(&mut self) {
+        if let Err(e) = self.cache.save() {
+            eprintln!("Unable to save the hash cache: {}", e);
         }
-        false
     }
 }
 
+impl<H> Iterator for KeyFinder<H>
+where
+    H: Hasher,
+{
+    type Item = Key;
+
+    fn next(&mut self) -> Option<Key> {
+        let key = self.find_key(self.next_from);
+        self.next_from = key.index + 1;
+        Some(key)
+    }
+}
+
+#[cfg(test)]
 fn stretched_hash(s: &[u8]) -> [u8; 16] {
     let mut hash = Md5::digest(s).to_vec();
     let mut hex = [0u8; 32];
@@ -163,9 +326,9 @@ fn stretched_hash(s: &[u8]) -> [u8; 16] {
             hex[2 * i] = b"0123456789abcdef"[(byte >> 4) as usize];
             hex[2 * i + 1] = b"0123456789abcdef"[(byte & 0x0F) as usize];
         }
-        hash = Md5::digest(&hex).to_vec();
+        hash = Md5::digest(hex).to_vec();
     }
-    Md5::digest(&hex).into()
+    Md5::digest(hex).into()
 }
 
 #[cfg(test)]
@@ -174,24 +337,105 @@ mod tests {
 
     #[test]
     fn test_find() {
-        let cache = Cache::new("abc", |buffer| Md5::digest(buffer).into());
+        let cache = Cache::new("abc", |buffer: &[u8]| Md5::digest(buffer).into());
         let mut finder = KeyFinder::new(cache);
         assert_eq!(finder.find_index(0), 39);
         assert_eq!(finder.find_index(40), 92);
     }
 
+    #[test]
+    fn test_stretched_batch() {
+        let inputs: Vec<Vec<u8>> = (0..6).map(|i| format!("abc{}", i).into_bytes()).collect();
+        let expected: Vec<[u8; 16]> = inputs.iter().map(|input| stretched_hash(input)).collect();
+        assert_eq!(StretchedMd5.hash_batch(&inputs), expected);
+    }
+
+    struct CountingMd5(std::cell::RefCell<Vec<usize>>);
+
+    impl Hasher for CountingMd5 {
+        fn hash_batch(&self, inputs: &[Vec<u8>]) -> Vec<[u8; 16]> {
+            self.0.borrow_mut().push(inputs.len());
+            inputs.iter().map(|input| Md5::digest(input).into()).collect()
+        }
+    }
+
+    #[test]
+    fn test_full_batches() {
+        let hasher = CountingMd5(Default::default());
+        let mut finder = KeyFinder::new(Cache::new("abc", hasher));
+        assert_eq!(finder.nth(63).unwrap().index, 22728);
+        let batches = finder.cache.hasher.0.borrow();
+        assert!(batches.iter().all(|&size| size == PREFETCH));
+        assert!(batches.len() > 22728 / PREFETCH);
+    }
+
+    #[test]
+    fn test_keys() {
+        let cache = Cache::new("abc", |buffer: &[u8]| Md5::digest(buffer).into());
+        let keys: Vec<Key> = KeyFinder::new(cache).take(64).collect();
+        assert_eq!(keys[0].index, 39);
+        assert_eq!(keys[0].triplet_nibble, 0xe);
+        assert_eq!(keys[0].confirming_index, 816);
+        assert!(keys[0].hash_hex.contains("eee"));
+        assert_eq!(keys[1].index, 92);
+        assert_eq!(keys[1].triplet_nibble, 0x9);
+        assert_eq!(keys[1].confirming_index, 200);
+        assert_eq!(keys[63].index, 22728);
+        for key in &keys {
+            assert!(key.confirming_index <= key.index + 1000);
+        }
+    }
+
     #[test]
     fn test_example_part1() {
-        assert_eq!(part1("abc"), 22728);
+        assert_eq!(part1("abc", None), 22728);
+    }
+
+    // Finds the 64th key through a cache persisted in `dir`, returning its
+    // index and the number of batches that had to be hashed.
+    fn persisted_run(dir: &Path) -> (usize, usize) {
+        let cache = Cache::new("abc", CountingMd5(Default::default())).persisted(Some(dir), 0);
+        let mut finder = KeyFinder::new(cache);
+        let index = finder.nth(63).unwrap().index;
+        finder.save_cache();
+        (index, finder.cache.hasher.0.borrow().len())
+    }
+
+    #[test]
+    fn test_example_part1_persisted() {
+        let dir = std::env::temp_dir().join(format!("day14-part1-{}", std::process::id()));
+        assert_eq!(part1("abc", Some(&dir)), 22728);
+        assert_eq!(part1("abc", Some(&dir)), 22728);
+        assert_eq!(persisted_run(&dir), (22728, 0));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_corrupted_cache_is_rebuilt() {
+        let dir = std::env::temp_dir().join(format!("day14-corrupted-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        let (index, batches) = persisted_run(&dir);
+        assert_eq!(index, 22728);
+        assert!(batches > 0);
+
+        let entry = std::fs::read_dir(&dir).unwrap().next().unwrap();
+        let path = entry.unwrap().path();
+        let mut bytes = std::fs::read(&path).unwrap();
+        let last = bytes.len() - 1;
+        bytes[last] ^= 0x01;
+        std::fs::write(&path, &bytes).unwrap();
+        assert_eq!(persisted_run(&dir), (22728, batches));
+        assert_eq!(persisted_run(&dir), (22728, 0));
+        std::fs::remove_dir_all(&dir).unwrap();
     }
 
     #[test]
     fn test_part1() {
-        assert_eq!(part1(&SALT), 23890);
+        assert_eq!(part1(&SALT, None), 23890);
     }
 
     #[test]
     fn test_part2() {
-        assert_eq!(part2(&SALT), 22696);
+        assert_eq!(part2(&SALT, None), 22696);
     }
 }
@@ -0,0 +1,223 @@
+// Multi-buffer MD5 specialised for the key stretching loop: every message is
+// the 32-byte lowercase hex of the previous digest, so it always fits in a
+// single padded block and LANES independent messages can share the rounds.
+
+pub const LANES: usize = 4;
+
+const INIT: [u32; 4] = [0x67452301, 0xefcdab89, 0x98badcfe, 0x10325476];
+
+#[rustfmt::skip]
+const K: [u32; 64] = [
+    0xd76aa478, 0xe8c7b756, 0x242070db, 0xc1bdceee, 0xf57c0faf, 0x4787c62a, 0xa8304613, 0xfd469501,
+    0x698098d8, 0x8b44f7af, 0xffff5bb1, 0x895cd7be, 0x6b901122, 0xfd987193, 0xa679438e, 0x49b40821,
+    0xf61e2562, 0xc040b340, 0x265e5a51, 0xe9b6c7aa, 0xd62f105d, 0x02441453, 0xd8a1e681, 0xe7d3fbc8,
+    0x21e1cde6, 0xc33707d6, 0xf4d50d87, 0x455a14ed, 0xa9e3e905, 0xfcefa3f8, 0x676f02d9, 0x8d2a4c8a,
+    0xfffa3942, 0x8771f681, 0x6d9d6122, 0xfde5380c, 0xa4beea44, 0x4bdecfa9, 0xf6bb4b60, 0xbebfbc70,
+    0x289b7ec6, 0xeaa127fa, 0xd4ef3085, 0x04881d05, 0xd9d4d039, 0xe6db99e5, 0x1fa27cf8, 0xc4ac5665,
+    0xf4292244, 0x432aff97, 0xab9423a7, 0xfc93a039, 0x655b59c3, 0x8f0ccc92, 0xffeff47d, 0x85845dd1,
+    0x6fa87e4f, 0xfe2ce6e0, 0xa3014314, 0x4e0811a1, 0xf7537e82, 0xbd3af235, 0x2ad7d2bb, 0xeb86d391,
+];
+
+#[rustfmt::skip]
+const S: [u32; 64] = [
+    7, 12, 17, 22, 7, 12, 17, 22, 7, 12, 17, 22, 7, 12, 17, 22,
+    5, 9, 14, 20, 5, 9, 14, 20, 5, 9, 14, 20, 5, 9, 14, 20,
+    4, 11, 16, 23, 4, 11, 16, 23, 4, 11, 16, 23, 4, 11, 16, 23,
+    6, 10, 15, 21, 6, 10, 15, 21, 6, 10, 15, 21, 6, 10, 15, 21,
+];
+
+fn message_index(i: usize) -> usize {
+    match i / 16 {
+        0 => i,
+        1 => (5 * i + 1) % 16,
+        2 => (3 * i + 5) % 16,
+        _ => (7 * i) % 16,
+    }
+}
+
+// Applies `rounds` iterations of "hash the hex of the hash" to every lane.
+pub fn stretch(hashes: &mut [[u8; 16]; LANES], rounds: usize) {
+    for _ in 0..rounds {
+        let mut words = [[0u32; LANES]; 8];
+        for (lane, hash) in hashes.iter().enumerate() {
+            let hex = to_hex(hash);
+            for (w, chunk) in hex.chunks_exact(4).enumerate() {
+                words[w][lane] = u32::from_le_bytes(chunk.try_into().unwrap());
+            }
+        }
+        let state = digest_block(&words);
+        for (lane, hash) in hashes.iter_mut().enumerate() {
+            for (w, word) in state.iter().enumerate() {
+                hash[4 * w..4 * w + 4].copy_from_slice(&word[lane].to_le_bytes());
+            }
+        }
+    }
+}
+
+pub fn to_hex(hash: &[u8; 16]) -> [u8; 32] {
+    const DIGITS: &[u8; 16] = b"0123456789abcdef";
+    let mut hex = [0u8; 32];
+    for (i, byte) in hash.iter().enumerate() {
+        hex[2 * i] = DIGITS[(byte >> 4) as usize];
+        hex[2 * i + 1] = DIGITS[(byte & 0x0F) as usize];
+    }
+    hex
+}
+
+#[cfg(target_arch = "x86_64")]
+fn digest_block(words: &[[u32; LANES]; 8]) -> [[u32; LANES]; 4] {
+    // SSE2 is part of the x86_64 baseline, so this path needs no runtime detection.
+    unsafe { sse2::digest_block(words) }
+}
+
+#[cfg(not(target_arch = "x86_64"))]
+fn digest_block(words: &[[u32; LANES]; 8]) -> [[u32; LANES]; 4] {
+    portable::digest_block(words)
+}
+
+#[cfg_attr(target_arch = "x86_64", allow(dead_code))]
+mod portable {
+    use super::{INIT, K, LANES, S, message_index};
+
+    type Lanes = [u32; LANES];
+
+    fn lanes(f: impl Fn(usize) -> u32) -> Lanes {
+        std::array::from_fn(f)
+    }
+
+    pub fn digest_block(words: &[[u32; LANES]; 8]) -> [Lanes; 4] {
+        let mut x = [[0u32; LANES]; 16];
+        x[..8].copy_from_slice(words);
+        x[8] = [0x80; LANES];
+        x[14] = [256; LANES];
+
+        let [mut a, mut b, mut c, mut d] = INIT.map(|v| [v; LANES]);
+        for i in 0..64 {
+            let f = match i / 16 {
+                0 => lanes(|l| (b[l] & c[l]) | (!b[l] & d[l])),
+                1 => lanes(|l| (d[l] & b[l]) | (!d[l] & c[l])),
+                2 => lanes(|l| b[l] ^ c[l] ^ d[l]),
+                _ => lanes(|l| c[l] ^ (b[l] | !d[l])),
+            };
+            let m = x[message_index(i)];
+            let rotated = lanes(|l| {
+                a[l].wrapping_add(f[l])
+                    .wrapping_add(K[i])
+                    .wrapping_add(m[l])
+                    .rotate_left(S[i])
+            });
+            a = d;
+            d = c;
+            c = b;
+            b = lanes(|l| b[l].wrapping_add(rotated[l]));
+        }
+        [
+            lanes(|l| a[l].wrapping_add(INIT[0])),
+            lanes(|l| b[l].wrapping_add(INIT[1])),
+            lanes(|l| c[l].wrapping_add(INIT[2])),
+            lanes(|l| d[l].wrapping_add(INIT[3])),
+        ]
+    }
+}
+
+#[cfg(target_arch = "x86_64")]
+mod sse2 {
+    use super::{INIT, K, LANES, S, message_index};
+    use std::arch::x86_64::*;
+
+    #[target_feature(enable = "sse2")]
+    fn rotate_left(v: __m128i, s: u32) -> __m128i {
+        let left = _mm_sll_epi32(v, _mm_cvtsi32_si128(s as i32));
+        let right = _mm_srl_epi32(v, _mm_cvtsi32_si128(32 - s as i32));
+        _mm_or_si128(left, right)
+    }
+
+    #[target_feature(enable = "sse2")]
+    fn load(lanes: &[u32; LANES]) -> __m128i {
+        _mm_set_epi32(
+            lanes[3] as i32,
+            lanes[2] as i32,
+            lanes[1] as i32,
+            lanes[0] as i32,
+        )
+    }
+
+    #[target_feature(enable = "sse2")]
+    fn store(v: __m128i) -> [u32; LANES] {
+        let mut out = [0u32; LANES];
+        unsafe { _mm_storeu_si128(out.as_mut_ptr() as *mut __m128i, v) };
+        out
+    }
+
+    #[target_feature(enable = "sse2")]
+    pub fn digest_block(words: &[[u32; LANES]; 8]) -> [[u32; LANES]; 4] {
+        let zero = _mm_setzero_si128();
+        let mut x = [zero; 16];
+        for (w, lanes) in words.iter().enumerate() {
+            x[w] = load(lanes);
+        }
+        x[8] = _mm_set1_epi32(0x80);
+        x[14] = _mm_set1_epi32(256);
+
+        let ones = _mm_set1_epi32(-1);
+        let init = INIT.map(|v| _mm_set1_epi32(v as i32));
+        let [mut a, mut b, mut c, mut d] = init;
+        for i in 0..64 {
+            let f = match i / 16 {
+                0 => _mm_or_si128(_mm_and_si128(b, c), _mm_andnot_si128(b, d)),
+                1 => _mm_or_si128(_mm_and_si128(d, b), _mm_andnot_si128(d, c)),
+                2 => _mm_xor_si128(_mm_xor_si128(b, c), d),
+                _ => _mm_xor_si128(c, _mm_or_si128(b, _mm_xor_si128(d, ones))),
+            };
+            let sum = _mm_add_epi32(
+                _mm_add_epi32(a, f),
+                _mm_add_epi32(_mm_set1_epi32(K[i] as i32), x[message_index(i)]),
+            );
+            a = d;
+            d = c;
+            c = b;
+            b = _mm_add_epi32(b, rotate_left(sum, S[i]));
+        }
+        [
+            store(_mm_add_epi32(a, init[0])),
+            store(_mm_add_epi32(b, init[1])),
+            store(_mm_add_epi32(c, init[2])),
+            store(_mm_add_epi32(d, init[3])),
+        ]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use md5::{Digest, Md5};
+
+    fn sample_hashes() -> [[u8; 16]; LANES] {
+        std::array::from_fn(|lane| Md5::digest(format!("abc{}", lane * 7).as_bytes()).into())
+    }
+
+    #[test]
+    fn test_stretch_matches_md5() {
+        let mut hashes = sample_hashes();
+        let expected = hashes.map(|mut hash| {
+            for _ in 0..5 {
+                hash = Md5::digest(to_hex(&hash)).into();
+            }
+            hash
+        });
+        stretch(&mut hashes, 5);
+        assert_eq!(hashes, expected);
+    }
+
+    #[test]
+    fn test_portable_matches_dispatch() {
+        let hashes = sample_hashes();
+        let mut words = [[0u32; LANES]; 8];
+        for (lane, hash) in hashes.iter().enumerate() {
+            for (w, chunk) in to_hex(hash).chunks_exact(4).enumerate() {
+                words[w][lane] = u32::from_le_bytes(chunk.try_into().unwrap());
+            }
+        }
+        assert_eq!(portable::digest_block(&words), digest_block(&words));
+    }
+}
@@ -0,0 +1,224 @@
+use crate::CacheEntry;
+use memmap2::Mmap;
+use std::fs::{self, File};
+use std::io;
+use std::path::{Path, PathBuf};
+
+// File layout (little-endian):
+//   magic [8] | version u32 | stretch u32 | salt length u32 | count u64 | checksum u64
+//   salt bytes | count entries of ENTRY_SIZE bytes (hash [16], first triplet or 0xFF,
+//   quintuplets u16)
+const MAGIC: &[u8; 8] = b"D14CACHE";
+const VERSION: u32 = 2;
+const HEADER_SIZE: usize = 36;
+const ENTRY_SIZE: usize = 19;
+const NO_TRIPLET: u8 = 0xFF;
+
+pub struct Store {
+    path: PathBuf,
+    salt: Vec<u8>,
+    stretch: u32,
+    mmap: Option<Mmap>,
+    persisted: usize,
+    pending: Vec<CacheEntry>,
+}
+
+impl Store {
+    pub fn open(dir: &Path, salt: &str, stretch: u32) -> io::Result<Self> {
+        fs::create_dir_all(dir)?;
+        let salt = salt.as_bytes().to_vec();
+        let path = dir.join(format!("day14-{:016x}-{}.bin", fnv1a(&salt), stretch));
+        let mut store = Self {
+            path,
+            salt,
+            stretch,
+            mmap: None,
+            persisted: 0,
+            pending: Vec::new(),
+        };
+        store.load()?;
+        Ok(store)
+    }
+
+    // Maps the existing file, leaving the store empty (to be rebuilt) when it
+    // is missing, was written for another salt or stretch, or is corrupted.
+    fn load(&mut self) -> io::Result<()> {
+        self.mmap = None;
+        self.persisted = 0;
+        let file = match File::open(&self.path) {
+            Ok(file) => file,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(()),
+            Err(e) => return Err(e),
+        };
+        // SAFETY: the file is only ever replaced atomically by `save`, never modified in place.
+        let mmap = unsafe { Mmap::map(&file)? };
+        if let Some(count) = self.validate(&mmap) {
+            self.persisted = count;
+            self.mmap = Some(mmap);
+        }
+        Ok(())
+    }
+
+    fn validate(&self, bytes: &[u8]) -> Option<usize> {
+        if bytes.len() < HEADER_SIZE || &bytes[0..8] != MAGIC {
+            return None;
+        }
+        let version = u32::from_le_bytes(bytes[8..12].try_into().unwrap());
+        let stretch = u32::from_le_bytes(bytes[12..16].try_into().unwrap());
+        let salt_length = u32::from_le_bytes(bytes[16..20].try_into().unwrap()) as usize;
+        let count = u64::from_le_bytes(bytes[20..28].try_into().unwrap()) as usize;
+        let checksum = u64::from_le_bytes(bytes[28..36].try_into().unwrap());
+        if version != VERSION || stretch != self.stretch || salt_length != self.salt.len() {
+            return None;
+        }
+        let entries_start = HEADER_SIZE + salt_length;
+        let expected_length = count.checked_mul(ENTRY_SIZE)?.checked_add(entries_start)?;
+        if bytes.len() != expected_length || bytes[HEADER_SIZE..entries_start] != self.salt[..] {
+            return None;
+        }
+        if fnv1a(&bytes[HEADER_SIZE..]) != checksum {
+            return None;
+        }
+        Some(count)
+    }
+
+    pub fn len(&self) -> usize {
+        self.persisted + self.pending.len()
+    }
+
+    pub fn get(&self, suffix: usize) -> Option<CacheEntry> {
+        if suffix < self.persisted {
+            let mmap = self.mmap.as_ref().unwrap();
+            let offset = HEADER_SIZE + self.salt.len() + suffix * ENTRY_SIZE;
+            Some(decode(&mmap[offset..offset + ENTRY_SIZE]))
+        } else {
+            self.pending.get(suffix - self.persisted).copied()
+        }
+    }
+
+    // Only contiguous runs are kept so that the file stays a dense array by suffix.
+    pub fn record(&mut self, suffix: usize, entry: CacheEntry) {
+        if suffix == self.len() {
+            self.pending.push(entry);
+        }
+    }
+
+    pub fn save(&mut self) -> io::Result<()> {
+        if self.pending.is_empty() {
+            return Ok(());
+        }
+        let count = self.len();
+        let mut body = Vec::with_capacity(self.salt.len() + count * ENTRY_SIZE);
+        body.extend_from_slice(&self.salt);
+        if let Some(mmap) = &self.mmap {
+            body.extend_from_slice(&mmap[HEADER_SIZE + self.salt.len()..]);
+        }
+        for entry in &self.pending {
+            body.extend_from_slice(&encode(entry));
+        }
+
+        let mut bytes = Vec::with_capacity(HEADER_SIZE + body.len());
+        bytes.extend_from_slice(MAGIC);
+        bytes.extend_from_slice(&VERSION.to_le_bytes());
+        bytes.extend_from_slice(&self.stretch.to_le_bytes());
+        bytes.extend_from_slice(&(self.salt.len() as u32).to_le_bytes());
+        bytes.extend_from_slice(&(count as u64).to_le_bytes());
+        bytes.extend_from_slice(&fnv1a(&body).to_le_bytes());
+        bytes.extend_from_slice(&body);
+
+        let tmp = self.path.with_extension("tmp");
+        fs::write(&tmp, &bytes)?;
+        self.mmap = None;
+        fs::rename(&tmp, &self.path)?;
+        self.pending.clear();
+        self.load()
+    }
+}
+
+fn encode(entry: &CacheEntry) -> [u8; ENTRY_SIZE] {
+    let mut bytes = [0; ENTRY_SIZE];
+    bytes[..16].copy_from_slice(&entry.hash);
+    bytes[16] = entry.first_triplet.unwrap_or(NO_TRIPLET);
+    bytes[17..].copy_from_slice(&entry.quintuplets.to_le_bytes());
+    bytes
+}
+
+fn decode(bytes: &[u8]) -> CacheEntry {
+    CacheEntry {
+        hash: bytes[..16].try_into().unwrap(),
+        first_triplet: (bytes[16] != NO_TRIPLET).then_some(bytes[16]),
+        quintuplets: u16::from_le_bytes([bytes[17], bytes[18]]),
+    }
+}
+
+fn fnv1a(bytes: &[u8]) -> u64 {
+    let mut hash = 0xcbf29ce484222325_u64;
+    for &byte in bytes {
+        hash ^= byte as u64;
+        hash = hash.wrapping_mul(0x100000001b3);
+    }
+    hash
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("day14-store-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        dir
+    }
+
+    fn entry(i: usize) -> CacheEntry {
+        CacheEntry {
+            hash: [i as u8; 16],
+            first_triplet: (!i.is_multiple_of(3)).then_some((i % 16) as u8),
+            quintuplets: (i * 37) as u16,
+        }
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let dir = temp_dir("round-trip");
+        let mut store = Store::open(&dir, "abc", 2016).unwrap();
+        for i in 0..10 {
+            store.record(i, entry(i));
+        }
+        store.record(20, entry(20));
+        store.save().unwrap();
+
+        let mut store = Store::open(&dir, "abc", 2016).unwrap();
+        assert_eq!(store.len(), 10);
+        for i in 0..10 {
+            assert_eq!(store.get(i), Some(entry(i)));
+        }
+        store.record(10, entry(10));
+        store.save().unwrap();
+        assert_eq!(
+            Store::open(&dir, "abc", 2016).unwrap().get(10),
+            Some(entry(10))
+        );
+        assert_eq!(Store::open(&dir, "abc", 0).unwrap().len(), 0);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_corrupted_file_is_rebuilt() {
+        let dir = temp_dir("corrupted");
+        let mut store = Store::open(&dir, "abc", 0).unwrap();
+        for i in 0..5 {
+            store.record(i, entry(i));
+        }
+        store.save().unwrap();
+        let path = store.path.clone();
+        drop(store);
+
+        let mut bytes = fs::read(&path).unwrap();
+        let last = bytes.len() - 1;
+        bytes[last] ^= 0x01;
+        fs::write(&path, &bytes).unwrap();
+        assert_eq!(Store::open(&dir, "abc", 0).unwrap().len(), 0);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
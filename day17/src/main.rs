@@ -1,6 +1,6 @@
 use md5::{Digest, Md5};
 use once_cell::sync::Lazy;
-use std::collections::VecDeque;
+use std::collections::{BTreeMap, VecDeque};
 
 static PASSCODE: Lazy<String> =
     Lazy::new(|| common::read_file_as_string("data/day17.txt").unwrap());
@@ -9,51 +9,230 @@ const GRID_WIDTH: usize = 4;
 const GRID_HEIGHT: usize = 4;
 
 fn main() {
-    println!("Part 1: {}", part1(&PASSCODE));
-    println!("Part 2: {}", part2(&PASSCODE));
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if args.is_empty() {
+        println!("Part 1: {}", part1(&PASSCODE));
+        println!("Part 2: {}", part2(&PASSCODE));
+        return;
+    }
+    let vault = vault_from_args(&args);
+    if let Some(i) = args.iter().position(|arg| arg == "--replay") {
+        print_replay(
+            &vault,
+            &PASSCODE,
+            args.get(i + 1).map_or("", String::as_str),
+        );
+        return;
+    }
+    let summary = vault.summary(&PASSCODE);
+    println!("Shortest: {:?}", vault.shortest_path(&PASSCODE));
+    println!("Paths: {}", summary.paths);
+    println!("Dead ends: {}", summary.dead_ends);
+    println!("Pruned: {}", summary.pruned);
+    for (length, count) in &summary.lengths {
+        println!("{:6}: {}", length, count);
+    }
 }
 
-fn part1(passcode: &str) -> String {
-    let mut queue = VecDeque::new();
-    let initial = Step {
-        path: passcode.as_bytes().to_vec(),
-        x: 1,
-        y: 1,
-    };
-    queue.push_back(initial);
-    while let Some(step) = queue.pop_front() {
-        if step.x == GRID_WIDTH && step.y == GRID_HEIGHT {
-            return std::str::from_utf8(&step.path[passcode.len()..])
-                .unwrap()
-                .to_string();
+fn print_replay(vault: &Vault, passcode: &str, route: &str) {
+    match replay(vault, passcode, route) {
+        Ok(frames) => {
+            for (i, frame) in frames.iter().enumerate() {
+                match frame.direction {
+                    None => println!("Start at ({}, {})", frame.x, frame.y),
+                    Some(d) => println!("Step {}: {} to ({}, {})", i, d, frame.x, frame.y),
+                }
+                println!("{}\n", frame.render(vault));
+            }
+            println!("Valid path of length {}", route.len());
+        }
+        Err(e) => {
+            eprintln!("Invalid path: {}", e);
+            std::process::exit(1);
         }
-        queue.extend(step.expand());
     }
-    unreachable!("No path found");
 }
 
-fn part2(passcode: &str) -> usize {
-    let mut queue = Vec::new();
-    let mut longest = 0;
-    let initial = Step {
-        path: passcode.as_bytes().to_vec(),
-        x: 1,
-        y: 1,
+// Accepts `--replay PATH`, `--size WxH`, `--start X,Y`, `--target X,Y` and `--max-depth N`.
+fn vault_from_args(args: &[String]) -> Vault {
+    fn pair(value: &str, separator: char) -> (usize, usize) {
+        let (a, b) = value.split_once(separator).expect("Invalid pair");
+        (a.parse().unwrap(), b.parse().unwrap())
+    }
+    let option = |name: &str| {
+        args.iter()
+            .position(|arg| arg == name)
+            .map(|i| args.get(i + 1).expect("Missing option value").as_str())
     };
-    queue.push(initial);
-    while let Some(step) = queue.pop() {
-        if step.x == GRID_WIDTH && step.y == GRID_HEIGHT {
-            let new_length = step.path.len() - passcode.len();
-            if longest < new_length {
-                longest = new_length;
+    let (width, height) = option("--size").map_or((GRID_WIDTH, GRID_HEIGHT), |v| pair(v, 'x'));
+    let mut vault = Vault::new(width, height);
+    if let Some((x, y)) = option("--start").map(|v| pair(v, ',')) {
+        vault = vault.with_start(x, y);
+    }
+    if let Some((x, y)) = option("--target").map(|v| pair(v, ',')) {
+        vault = vault.with_target(x, y);
+    }
+    if let Some(max_depth) = option("--max-depth") {
+        vault = vault.with_max_depth(max_depth.parse().unwrap());
+    }
+    vault
+}
+
+fn part1(passcode: &str) -> String {
+    Vault::new(GRID_WIDTH, GRID_HEIGHT)
+        .shortest_path(passcode)
+        .expect("No path found")
+}
+
+fn part2(passcode: &str) -> usize {
+    Vault::new(GRID_WIDTH, GRID_HEIGHT)
+        .longest_path_length(passcode)
+        .unwrap_or(0)
+}
+
+// Positions are 1-based, as in the puzzle's 4x4 grid where the vault sits at (4, 4).
+#[derive(Debug, Clone, Copy)]
+struct Vault {
+    width: usize,
+    height: usize,
+    start: (usize, usize),
+    target: (usize, usize),
+    max_depth: Option<usize>,
+}
+
+#[derive(Debug, Default)]
+struct Summary {
+    paths: usize,
+    lengths: BTreeMap<usize, usize>,
+    dead_ends: usize,
+    pruned: usize,
+}
+
+impl Vault {
+    fn new(width: usize, height: usize) -> Self {
+        assert!(width > 0 && height > 0, "Empty grid {}x{}", width, height);
+        Self {
+            width,
+            height,
+            start: (1, 1),
+            target: (width, height),
+            max_depth: None,
+        }
+    }
+
+    fn with_start(mut self, x: usize, y: usize) -> Self {
+        assert!(self.contains(x, y), "Start ({}, {}) outside the grid", x, y);
+        self.start = (x, y);
+        self
+    }
+
+    fn with_target(mut self, x: usize, y: usize) -> Self {
+        assert!(
+            self.contains(x, y),
+            "Target ({}, {}) outside the grid",
+            x,
+            y
+        );
+        self.target = (x, y);
+        self
+    }
+
+    fn with_max_depth(mut self, max_depth: usize) -> Self {
+        self.max_depth = Some(max_depth);
+        self
+    }
+
+    fn contains(&self, x: usize, y: usize) -> bool {
+        (1..=self.width).contains(&x) && (1..=self.height).contains(&y)
+    }
+
+    fn initial(&self, passcode: &str) -> Step {
+        Step {
+            path: passcode.as_bytes().to_vec(),
+            x: self.start.0,
+            y: self.start.1,
+        }
+    }
+
+    fn shortest_path(&self, passcode: &str) -> Option<String> {
+        let mut queue = VecDeque::new();
+        queue.push_back(self.initial(passcode));
+        while let Some(step) = queue.pop_front() {
+            if (step.x, step.y) == self.target {
+                return Some(step.route(passcode).to_string());
+            }
+            if self
+                .max_depth
+                .is_none_or(|max| step.path.len() - passcode.len() < max)
+            {
+                queue.extend(step.expand(self));
+            }
+        }
+        None
+    }
+
+    fn longest_path_length(&self, passcode: &str) -> Option<usize> {
+        self.paths(passcode).map(|path| path.len()).max()
+    }
+
+    fn paths(&self, passcode: &str) -> Paths<'_> {
+        Paths {
+            vault: self,
+            prefix_length: passcode.len(),
+            stack: vec![self.initial(passcode)],
+            dead_ends: 0,
+            pruned: 0,
+        }
+    }
+
+    fn summary(&self, passcode: &str) -> Summary {
+        let mut paths = self.paths(passcode);
+        let mut summary = Summary::default();
+        for path in paths.by_ref() {
+            summary.paths += 1;
+            *summary.lengths.entry(path.len()).or_insert(0) += 1;
+        }
+        summary.dead_ends = paths.dead_ends;
+        summary.pruned = paths.pruned;
+        summary
+    }
+}
+
+// Depth-first enumeration of every path that reaches the vault. Paths stop at
+// the vault, so every yielded route is distinct.
+struct Paths<'a> {
+    vault: &'a Vault,
+    prefix_length: usize,
+    stack: Vec<Step>,
+    dead_ends: usize,
+    pruned: usize,
+}
+
+impl Iterator for Paths<'_> {
+    type Item = String;
+
+    fn next(&mut self) -> Option<String> {
+        while let Some(step) = self.stack.pop() {
+            let length = step.path.len() - self.prefix_length;
+            if (step.x, step.y) == self.vault.target {
+                return Some(String::from_utf8(step.path[self.prefix_length..].to_vec()).unwrap());
+            }
+            if self.vault.max_depth.is_some_and(|max| length >= max) {
+                self.pruned += 1;
+                continue;
+            }
+            let next = step.expand(self.vault);
+            if next.is_empty() {
+                self.dead_ends += 1;
             }
-        } else {
-            queue.extend(step.expand());
+            self.stack.extend(next);
         }
+        None
     }
-    longest
 }
 
+const ALL_DIRECTIONS: [u8; 4] = [b'U', b'D', b'L', b'R'];
+
 struct Step {
     path: Vec<u8>,
     x: usize,
@@ -61,57 +240,160 @@ struct Step {
 }
 
 impl Step {
-    fn expand(&self) -> Vec<Step> {
-        const ALL_DIRECTIONS: [u8; 4] = [b'U', b'D', b'L', b'R'];
+    fn route<'a>(&'a self, passcode: &str) -> &'a str {
+        std::str::from_utf8(&self.path[passcode.len()..]).unwrap()
+    }
+
+    fn open_doors(&self) -> [bool; 4] {
         let hash = Md5::digest(self.path.as_slice());
         let nibbles = [hash[0] >> 4, hash[0] & 0x0F, hash[1] >> 4, hash[1] & 0x0F];
-        let mut steps = Vec::new();
-        for (i, nibble) in nibbles.iter().enumerate() {
-            if *nibble < 11 {
-                continue;
+        nibbles.map(|nibble| nibble >= 11)
+    }
+
+    // Moves through the given door ignoring whether it is locked; None if it leads into a wall.
+    fn walk(&self, direction: u8, vault: &Vault) -> Option<Step> {
+        let (x, y) = match direction {
+            b'U' if self.y > 1 => (self.x, self.y - 1),
+            b'D' if self.y < vault.height => (self.x, self.y + 1),
+            b'L' if self.x > 1 => (self.x - 1, self.y),
+            b'R' if self.x < vault.width => (self.x + 1, self.y),
+            _ => return None,
+        };
+        let mut path = self.path.clone();
+        path.push(direction);
+        Some(Step { path, x, y })
+    }
+
+    fn expand(&self, vault: &Vault) -> Vec<Step> {
+        ALL_DIRECTIONS
+            .iter()
+            .zip(self.open_doors())
+            .filter(|(_, open)| *open)
+            .filter_map(|(&direction, _)| self.walk(direction, vault))
+            .collect()
+    }
+}
+
+#[derive(Debug, PartialEq)]
+enum ReplayError {
+    UnknownDirection { step: usize, direction: char },
+    Wall { step: usize, direction: char },
+    LockedDoor { step: usize, direction: char },
+    PassedVault { step: usize },
+    NotAtVault { x: usize, y: usize },
+}
+
+impl std::fmt::Display for ReplayError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ReplayError::UnknownDirection { step, direction } => {
+                write!(f, "step {}: unknown direction {:?}", step, direction)
             }
-            match ALL_DIRECTIONS[i] {
-                b'U' if self.y > 1 => {
-                    let mut new_path = self.path.clone();
-                    new_path.push(b'U');
-                    steps.push(Step {
-                        path: new_path,
-                        x: self.x,
-                        y: self.y - 1,
-                    });
-                }
-                b'D' if self.y < GRID_HEIGHT => {
-                    let mut new_path = self.path.clone();
-                    new_path.push(b'D');
-                    steps.push(Step {
-                        path: new_path,
-                        x: self.x,
-                        y: self.y + 1,
-                    });
-                }
-                b'L' if self.x > 1 => {
-                    let mut new_path = self.path.clone();
-                    new_path.push(b'L');
-                    steps.push(Step {
-                        path: new_path,
-                        x: self.x - 1,
-                        y: self.y,
-                    });
+            ReplayError::Wall { step, direction } => {
+                write!(f, "step {}: {} walks into a wall", step, direction)
+            }
+            ReplayError::LockedDoor { step, direction } => {
+                write!(f, "step {}: door {} is locked", step, direction)
+            }
+            ReplayError::PassedVault { step } => {
+                write!(f, "step {}: the vault was already reached", step)
+            }
+            ReplayError::NotAtVault { x, y } => {
+                write!(f, "path ends at ({}, {}) instead of the vault", x, y)
+            }
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+struct Frame {
+    direction: Option<char>,
+    x: usize,
+    y: usize,
+    doors: [bool; 4],
+}
+
+impl Frame {
+    fn new(step: &Step, direction: Option<char>) -> Self {
+        Self {
+            direction,
+            x: step.x,
+            y: step.y,
+            doors: step.open_doors(),
+        }
+    }
+
+    // Draws the vault as in the puzzle statement: the current room is `S`, the
+    // vault is `V`, and the doors of the current room are blank when open and
+    // `#` when locked.
+    fn render(&self, vault: &Vault) -> String {
+        let (width, height) = (2 * vault.width + 1, 2 * vault.height + 1);
+        let mut grid = vec![vec![b'#'; width]; height];
+        for y in 1..=vault.height {
+            for x in 1..=vault.width {
+                grid[2 * y - 1][2 * x - 1] = b' ';
+                if x < vault.width {
+                    grid[2 * y - 1][2 * x] = b'|';
                 }
-                b'R' if self.x < GRID_WIDTH => {
-                    let mut new_path = self.path.clone();
-                    new_path.push(b'R');
-                    steps.push(Step {
-                        path: new_path,
-                        x: self.x + 1,
-                        y: self.y,
-                    });
+                if y < vault.height {
+                    grid[2 * y][2 * x - 1] = b'-';
                 }
-                _ => continue,
             }
         }
-        steps
+        let (tx, ty) = vault.target;
+        grid[2 * ty - 1][2 * tx - 1] = b'V';
+        let (cx, cy) = (2 * self.x - 1, 2 * self.y - 1);
+        grid[cy][cx] = b'S';
+        let neighbours = [(cx, cy - 1), (cx, cy + 1), (cx - 1, cy), (cx + 1, cy)];
+        for ((nx, ny), open) in neighbours.into_iter().zip(self.doors) {
+            if grid[ny][nx] != b'#' {
+                grid[ny][nx] = if open { b' ' } else { b'#' };
+            }
+        }
+        grid.into_iter()
+            .map(|row| String::from_utf8(row).unwrap())
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+}
+
+fn replay(vault: &Vault, passcode: &str, route: &str) -> Result<Vec<Frame>, ReplayError> {
+    let mut step = vault.initial(passcode);
+    let mut frames = vec![Frame::new(&step, None)];
+    for (i, direction) in route.chars().enumerate() {
+        let number = i + 1;
+        if (step.x, step.y) == vault.target {
+            return Err(ReplayError::PassedVault { step: number });
+        }
+        let door = ALL_DIRECTIONS
+            .iter()
+            .position(|&d| d as char == direction)
+            .ok_or(ReplayError::UnknownDirection {
+                step: number,
+                direction,
+            })?;
+        let next = step
+            .walk(ALL_DIRECTIONS[door], vault)
+            .ok_or(ReplayError::Wall {
+                step: number,
+                direction,
+            })?;
+        if !step.open_doors()[door] {
+            return Err(ReplayError::LockedDoor {
+                step: number,
+                direction,
+            });
+        }
+        step = next;
+        frames.push(Frame::new(&step, Some(direction)));
+    }
+    if (step.x, step.y) != vault.target {
+        return Err(ReplayError::NotAtVault {
+            x: step.x,
+            y: step.y,
+        });
     }
+    Ok(frames)
 }
 
 #[cfg(test)]
@@ -137,6 +419,90 @@ mod tests {
         assert_eq!(part2("ulqzkmiv"), 830);
     }
 
+    #[test]
+    fn test_summary() {
+        let summary = Vault::new(GRID_WIDTH, GRID_HEIGHT).summary("ihgpwlah");
+        assert_eq!(summary.lengths.keys().next(), Some(&6));
+        assert_eq!(summary.lengths.keys().last(), Some(&370));
+        assert_eq!(summary.paths, summary.lengths.values().sum::<usize>());
+        assert_eq!(summary.pruned, 0);
+
+        let pruned = Vault::new(GRID_WIDTH, GRID_HEIGHT)
+            .with_max_depth(10)
+            .summary("ihgpwlah");
+        assert!(pruned.paths > 0 && pruned.pruned > 0);
+        assert!(pruned.lengths.keys().all(|&length| length <= 10));
+    }
+
+    #[test]
+    fn test_custom_vault() {
+        let vault = Vault::new(1, 1);
+        assert_eq!(vault.shortest_path("hijkl"), Some(String::new()));
+
+        // "hijkl" only opens U, D and L from the start; D leads to (1, 2).
+        let vault = Vault::new(3, 3).with_target(1, 2);
+        assert_eq!(vault.shortest_path("hijkl"), Some("D".to_string()));
+        assert!(vault.paths("hijkl").all(|path| path.starts_with('D')));
+
+        let vault = Vault::new(GRID_WIDTH, GRID_HEIGHT)
+            .with_start(1, 1)
+            .with_max_depth(5);
+        assert_eq!(vault.shortest_path("ihgpwlah"), None);
+    }
+
+    #[test]
+    fn test_replay() {
+        let vault = Vault::new(GRID_WIDTH, GRID_HEIGHT);
+        let frames = replay(&vault, "ihgpwlah", "DDRRRD").unwrap();
+        assert_eq!(frames.len(), 7);
+        assert_eq!((frames[6].x, frames[6].y), (4, 4));
+        assert_eq!(
+            frames[0].render(&vault),
+            [
+                "#########",
+                "#S  | | #",
+                "# #-#-#-#",
+                "# | | | #",
+                "#-#-#-#-#",
+                "# | | | #",
+                "#-#-#-#-#",
+                "# | | |V#",
+                "#########"
+            ]
+            .join("\n")
+        );
+
+        assert_eq!(
+            replay(&vault, "hijkl", "R"),
+            Err(ReplayError::LockedDoor {
+                step: 1,
+                direction: 'R'
+            })
+        );
+        assert_eq!(
+            replay(&vault, "hijkl", "U"),
+            Err(ReplayError::Wall {
+                step: 1,
+                direction: 'U'
+            })
+        );
+        assert_eq!(
+            replay(&vault, "hijkl", "DX"),
+            Err(ReplayError::UnknownDirection {
+                step: 2,
+                direction: 'X'
+            })
+        );
+        assert_eq!(
+            replay(&vault, "ihgpwlah", "DDRRRDU"),
+            Err(ReplayError::PassedVault { step: 7 })
+        );
+        assert_eq!(
+            replay(&vault, "hijkl", "D"),
+            Err(ReplayError::NotAtVault { x: 1, y: 2 })
+        );
+    }
+
     #[test]
     fn test_part2() {
         assert_eq!(part2(&PASSCODE), 536);